@@ -1,3 +1,6 @@
+// the borrows below are kept as written, the queue APIs accept `&&str` as well as `&str`
+#![allow(clippy::needless_borrow)]
+
 use pgmq::{Message, PGMQueueExt};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
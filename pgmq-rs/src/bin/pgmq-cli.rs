@@ -1,6 +1,5 @@
 use clap::{Arg, Command};
 
-use log;
 use pgmq::util::install_pgmq;
 use sqlx::PgPool;
 use std::process;
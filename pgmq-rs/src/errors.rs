@@ -21,6 +21,86 @@ pub enum PgmqError {
     #[error("invalid queue name: '{name}'")]
     InvalidQueueName { name: String },
 
+    /// a message whose `type` tag does not match any variant of the type it was read into
+    #[error("unknown message tag '{tag}' on message {msg_id}")]
+    UnknownMessageTag { tag: String, msg_id: i64 },
+
+    /// a function name error
+    /// only functions in the pgmq schema with lowercase and underscore names can be called
+    #[error("invalid function name: '{name}'")]
+    InvalidFunctionName { name: String },
+
+    /// a json key error
+    /// keys must be alphanumeric or underscores
+    #[error("invalid json key: '{key}'")]
+    InvalidJsonKey { key: String },
+
+    /// a read quantity error
+    /// the number of messages to read must be at least 1
+    #[error("invalid read quantity: {qty}, must be at least 1")]
+    InvalidQuantity { qty: i32 },
+
+    /// a duration argument is out of range
+    #[error("invalid {name}: {duration:?}, {reason}")]
+    InvalidDuration {
+        name: &'static str,
+        duration: std::time::Duration,
+        reason: &'static str,
+    },
+
+    /// the queue does not exist
+    #[error("queue '{name}' does not exist")]
+    QueueNotFound { name: String },
+
+    /// a partition error
+    /// the partition is not a partition of the queue's table
+    #[error("'{partition_name}' is not a partition of queue '{queue_name}'")]
+    UnknownPartition {
+        queue_name: String,
+        partition_name: String,
+    },
+
+    /// the `message` column of a queue table has a different type than the read expects
+    #[error("message column of queue '{name}' is {found}, expected {expected}")]
+    UnexpectedColumnType {
+        name: String,
+        expected: String,
+        found: String,
+    },
+
+    /// two queues cannot be swapped with `PGMQueueExt::swap_queues`
+    #[error("cannot swap queues '{a}' and '{b}': {reason}")]
+    IncompatibleQueues {
+        a: String,
+        b: String,
+        reason: String,
+    },
+
+    /// a message is larger than the limit configured on the client
+    #[error("message of {size} bytes exceeds the limit of {limit} bytes")]
+    MessageTooLarge { size: usize, limit: usize },
+
+    /// the message at the head of the queue is locked by another transaction, see
+    /// `PGMQueueExt::read_nowait`
+    #[error("head of queue '{name}' is locked by another transaction")]
+    WouldBlock { name: String },
+
+    /// an operation did not complete within its time limit
+    #[error("operation timed out after {timeout:?}")]
+    Timeout { timeout: std::time::Duration },
+
+    /// environment variables read by `PGMQueueExt::from_env` are missing or invalid
+    #[error("invalid environment: {}", .problems.join("; "))]
+    EnvironmentError { problems: Vec<String> },
+
+    /// an error reading or writing a stream, see `PGMQueueExt::export_ndjson`
+    #[error("io error {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// a line read by `PGMQueueExt::import_ndjson` is not a valid exported message
+    #[error("invalid message on line {line}: {reason}")]
+    ImportError { line: u64, reason: String },
+
     /// a reqwest error (only when the `cli` feature is enabled)
     #[cfg(feature = "cli")]
     #[error("http request error {0}")]
@@ -29,6 +109,108 @@ pub enum PgmqError {
     /// a general error for installation operations
     #[error("installation error: {0}")]
     InstallationError(String),
+
+    /// the message of an error that could not be cloned, see the `Clone` implementation
+    #[error("{0}")]
+    Other(String),
+}
+
+impl PgmqError {
+    /// Whether the operation that produced this error may succeed if retried.
+    ///
+    /// True for timeouts, locked rows, connection failures, pool timeouts and transient database errors such as
+    /// serialization failures, deadlocks or lock timeouts. False for everything else,
+    /// including parsing errors, invalid input and installation errors.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PgmqError::DatabaseError(e) => match e {
+                sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::WorkerCrashed => true,
+                sqlx::Error::Database(e) => e.code().is_some_and(|code| {
+                    // connection_exception, insufficient_resources
+                    code.starts_with("08")
+                        || code.starts_with("53")
+                        // serialization_failure, deadlock_detected, lock_not_available
+                        || matches!(code.as_ref(), "40001" | "40P01" | "55P03")
+                        // admin_shutdown, crash_shutdown, cannot_connect_now
+                        || matches!(code.as_ref(), "57P01" | "57P02" | "57P03")
+                }),
+                _ => false,
+            },
+            PgmqError::Timeout { .. } | PgmqError::WouldBlock { .. } => true,
+            #[cfg(feature = "cli")]
+            PgmqError::HttpError(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+}
+
+/// Errors wrapping a `sqlx`, `serde_json`, `std::io` or `reqwest` error cannot be cloned as-is,
+/// they are cloned into [`PgmqError::Other`] holding their message.
+impl Clone for PgmqError {
+    fn clone(&self) -> Self {
+        match self {
+            PgmqError::UrlParsingError(e) => PgmqError::UrlParsingError(*e),
+            PgmqError::InvalidQueueName { name } => {
+                PgmqError::InvalidQueueName { name: name.clone() }
+            }
+            PgmqError::UnknownMessageTag { tag, msg_id } => PgmqError::UnknownMessageTag {
+                tag: tag.clone(),
+                msg_id: *msg_id,
+            },
+            PgmqError::InvalidFunctionName { name } => {
+                PgmqError::InvalidFunctionName { name: name.clone() }
+            }
+            PgmqError::InvalidJsonKey { key } => PgmqError::InvalidJsonKey { key: key.clone() },
+            PgmqError::InvalidQuantity { qty } => PgmqError::InvalidQuantity { qty: *qty },
+            PgmqError::InvalidDuration {
+                name,
+                duration,
+                reason,
+            } => PgmqError::InvalidDuration {
+                name,
+                duration: *duration,
+                reason,
+            },
+            PgmqError::QueueNotFound { name } => PgmqError::QueueNotFound { name: name.clone() },
+            PgmqError::UnknownPartition {
+                queue_name,
+                partition_name,
+            } => PgmqError::UnknownPartition {
+                queue_name: queue_name.clone(),
+                partition_name: partition_name.clone(),
+            },
+            PgmqError::UnexpectedColumnType {
+                name,
+                expected,
+                found,
+            } => PgmqError::UnexpectedColumnType {
+                name: name.clone(),
+                expected: expected.clone(),
+                found: found.clone(),
+            },
+            PgmqError::IncompatibleQueues { a, b, reason } => PgmqError::IncompatibleQueues {
+                a: a.clone(),
+                b: b.clone(),
+                reason: reason.clone(),
+            },
+            PgmqError::MessageTooLarge { size, limit } => PgmqError::MessageTooLarge {
+                size: *size,
+                limit: *limit,
+            },
+            PgmqError::WouldBlock { name } => PgmqError::WouldBlock { name: name.clone() },
+            PgmqError::Timeout { timeout } => PgmqError::Timeout { timeout: *timeout },
+            PgmqError::EnvironmentError { problems } => PgmqError::EnvironmentError {
+                problems: problems.clone(),
+            },
+            PgmqError::ImportError { line, reason } => PgmqError::ImportError {
+                line: *line,
+                reason: reason.clone(),
+            },
+            PgmqError::InstallationError(e) => PgmqError::InstallationError(e.clone()),
+            PgmqError::Other(e) => PgmqError::Other(e.clone()),
+            e => PgmqError::Other(e.to_string()),
+        }
+    }
 }
 
 impl From<Box<dyn std::error::Error>> for PgmqError {
@@ -42,3 +224,68 @@ impl From<String> for PgmqError {
         PgmqError::InstallationError(err)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clone_keeps_message() {
+        let err = PgmqError::from(serde_json::from_str::<u32>("nope").unwrap_err());
+        let cloned = err.clone();
+        assert!(matches!(cloned, PgmqError::Other(_)));
+        assert_eq!(cloned.to_string(), err.to_string());
+
+        let err = PgmqError::InvalidQueueName {
+            name: "bad name".to_owned(),
+        };
+        assert!(matches!(err.clone(), PgmqError::InvalidQueueName { name } if name == "bad name"));
+
+        let err = PgmqError::InvalidDuration {
+            name: "lease",
+            duration: std::time::Duration::from_secs(u64::MAX),
+            reason: "must be at most i32::MAX seconds",
+        };
+        assert!(matches!(
+            err.clone(),
+            PgmqError::InvalidDuration { name: "lease", .. }
+        ));
+        assert_eq!(err.clone().to_string(), err.to_string());
+    }
+
+    #[test]
+    fn source_is_chained() {
+        use std::error::Error;
+
+        let err = PgmqError::from(serde_json::from_str::<u32>("nope").unwrap_err());
+        let source = err.source().expect("json error has a source");
+        assert!(source.downcast_ref::<serde_json::Error>().is_some());
+
+        let err = PgmqError::from(sqlx::Error::PoolTimedOut);
+        let source = err.source().expect("database error has a source");
+        assert!(matches!(
+            source.downcast_ref::<sqlx::Error>(),
+            Some(sqlx::Error::PoolTimedOut)
+        ));
+    }
+
+    #[test]
+    fn retryable() {
+        assert!(PgmqError::from(sqlx::Error::PoolTimedOut).is_retryable());
+        assert!(
+            PgmqError::from(sqlx::Error::Io(std::io::ErrorKind::ConnectionReset.into()))
+                .is_retryable()
+        );
+        assert!(!PgmqError::from(sqlx::Error::RowNotFound).is_retryable());
+        assert!(!PgmqError::from(serde_json::from_str::<u32>("nope").unwrap_err()).is_retryable());
+        assert!(!PgmqError::InvalidQueueName {
+            name: "bad name".to_owned()
+        }
+        .is_retryable());
+        assert!(!PgmqError::InstallationError("missing".to_owned()).is_retryable());
+        assert!(PgmqError::Timeout {
+            timeout: std::time::Duration::from_secs(1)
+        }
+        .is_retryable());
+    }
+}
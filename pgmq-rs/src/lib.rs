@@ -161,19 +161,30 @@ pub mod util;
 mod query;
 
 pub use errors::PgmqError;
-pub use pg_ext::PGMQueueExt;
+pub use pg_ext::{MessageGuard, PGMQueueExt};
 pub use types::Message;
 
 use std::time::Duration;
 
 /// Main controller for interacting with a queue.
 #[deprecated(note = "Use `pg_ext::PGMQueueExt` instead")]
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct PGMQueue {
     pub url: String,
     pub connection: Pool<Postgres>,
 }
 
+/// The password in `url` is redacted, see [`util::redact_url`].
+#[allow(deprecated)]
+impl std::fmt::Debug for PGMQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PGMQueue")
+            .field("url", &util::redact_url(&self.url))
+            .field("connection", &self.connection)
+            .finish()
+    }
+}
+
 #[allow(deprecated)]
 impl PGMQueue {
     pub async fn new(url: String) -> Result<Self, PgmqError> {
@@ -662,6 +673,7 @@ impl PGMQueue {
             Some(t) => t,
             None => types::VT_DEFAULT,
         };
+        util::check_qty(num_msgs)?;
         let query = &query::read(queue_name, vt_, num_msgs)?;
         let messages = fetch_messages::<T>(query, &self.connection).await?;
         Ok(messages)
@@ -686,6 +698,7 @@ impl PGMQueue {
         let vt_ = vt.unwrap_or(types::VT_DEFAULT);
         let poll_timeout_ = poll_timeout.unwrap_or(types::POLL_TIMEOUT_DEFAULT);
         let poll_interval_ = poll_interval.unwrap_or(types::POLL_INTERVAL_DEFAULT);
+        util::check_qty(max_batch_size)?;
         let start_time = std::time::Instant::now();
         loop {
             let query = &query::read(queue_name, vt_, max_batch_size)?;
@@ -1,23 +1,149 @@
 use crate::errors::PgmqError;
-use crate::types::{Message, QUEUE_PREFIX};
+use crate::types::{
+    ArchiveResult, AsQueueName, BatchSendResult, Clock, ConsumerLag, EmptyQueueBackoff,
+    ExportedMessage, LeasedMessage, Message, MessageFilter, MessageMeta, MsgIdRange, PartitionInfo,
+    PgmqArg, PgmqClientStats, PoppedBatch, QueueSize, QueueSpec, QueueType, ReadLock, ReadOrder,
+    SystemClock, ARCHIVE_PREFIX, CLAIMED_AT_HEADER, CLAIMED_BY_HEADER, DATABASE_URL_ENV,
+    DEDUP_KEY_HEADER, EXPIRES_AT_HEADER, MAX_CONNECTIONS_DEFAULT, MAX_CONNECTIONS_ENV,
+    PAUSED_QUEUES_TABLE, PGMQ_SCHEMA, POLL_INTERVAL_DEFAULT, POLL_TIMEOUT_DEFAULT, PRIORITY_HEADER,
+    QUARANTINE_TABLE, QUEUE_PREFIX,
+};
+use crate::util::{check_input, check_json_key, check_qty, connect, redact_url, socket_url};
 #[cfg(feature = "cli")]
-use crate::util::install_pgmq;
-use crate::util::{check_input, connect};
-use log::info;
+use crate::util::{install_pgmq, install_pgmq_fresh};
+use futures_util::stream::{self, Stream};
+use futures_util::{FutureExt, TryStreamExt};
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sqlx::postgres::PgRow;
 use sqlx::types::chrono::Utc;
 use sqlx::{Pool, Postgres, Row};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{Semaphore, SemaphorePermit};
 
 const DEFAULT_POLL_TIMEOUT_S: i32 = 5;
 const DEFAULT_POLL_INTERVAL_MS: i32 = 250;
+/// How long `consume_batches_until` trusts that a queue is not paused before checking again.
+const PAUSE_CHECK_INTERVAL: std::time::Duration = POLL_TIMEOUT_DEFAULT;
+/// First version of the extension that ships `pgmq.read_with_poll`.
+const MIN_READ_WITH_POLL_VERSION: &str = "0.25.0";
+/// First version of the extension whose `pgmq.set_vt` accepts a timestamp.
+const MIN_SET_VT_TIMESTAMP_VERSION: &str = "1.10.0";
+/// First version of the extension whose `pgmq.enable_notify_insert` accepts a throttle.
+const MIN_NOTIFY_INSERT_VERSION: &str = "1.8.0";
+/// Field that `read_tagged` expects the enum tag in.
+const MESSAGE_TAG_FIELD: &str = "type";
+/// SQLSTATE `undefined_function`
+const UNDEFINED_FUNCTION: &str = "42883";
+/// SQLSTATE `lock_not_available`
+const LOCK_NOT_AVAILABLE: &str = "55P03";
+/// SQLSTATE `query_canceled`, raised when `statement_timeout` expires
+const QUERY_CANCELED: &str = "57014";
+/// SQLSTATE `undefined_table`
+const UNDEFINED_TABLE: &str = "42P01";
+/// SQLSTATE `undefined_column`
+const UNDEFINED_COLUMN: &str = "42703";
 
 /// Main controller for interacting with a managed by the PGMQ Postgres extension.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct PGMQueueExt {
     pub url: String,
+    /// Prefer [`PGMQueueExt::pool`], direct access to this field may be removed in the future.
     pub connection: Pool<Postgres>,
+    counters: Arc<ClientCounters>,
+    /// Shared between clones, see [`PGMQueueExt::with_max_concurrent_polls`].
+    poll_permits: Option<Arc<Semaphore>>,
+    /// See [`PGMQueueExt::with_max_message_size`].
+    max_message_size: Option<usize>,
+    /// See [`PGMQueueExt::with_poll_statement_timeout_margin`].
+    poll_statement_timeout_margin: Option<std::time::Duration>,
+    /// See [`PGMQueueExt::with_slow_query_threshold`].
+    slow_query_threshold: Option<std::time::Duration>,
+    /// [`SystemClock`] unless replaced for tests with `with_clock`.
+    clock: Arc<dyn Clock>,
+}
+
+/// The password in `url` is redacted, see [`redact_url`].
+impl std::fmt::Debug for PGMQueueExt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PGMQueueExt")
+            .field("url", &redact_url(&self.url))
+            .field("connection", &self.connection)
+            .field("counters", &self.counters)
+            .field("poll_permits", &self.poll_permits)
+            .field("max_message_size", &self.max_message_size)
+            .field(
+                "poll_statement_timeout_margin",
+                &self.poll_statement_timeout_margin,
+            )
+            .field("slow_query_threshold", &self.slow_query_threshold)
+            .field("clock", &self.clock)
+            .finish()
+    }
+}
+
+/// A message read with [`PGMQueueExt::read_guard`] that must be acknowledged.
+///
+/// Dereferences to the underlying [`Message`]. Call [`ack`](Self::ack) to delete the message
+/// once it was processed, or [`nack`](Self::nack) to make it visible again right away.
+/// `Drop` cannot run async code, so a guard that is dropped without either simply lets the
+/// visibility timeout expire, after which the message is delivered again.
+#[derive(Debug)]
+pub struct MessageGuard<T = serde_json::Value> {
+    queue: PGMQueueExt,
+    queue_name: String,
+    message: Message<T>,
+}
+
+impl<T> MessageGuard<T> {
+    /// Delete the message from the queue.
+    pub async fn ack(self) -> Result<bool, PgmqError> {
+        self.queue
+            .delete(&self.queue_name, self.message.msg_id)
+            .await
+    }
+
+    /// Make the message visible again immediately.
+    pub async fn nack(self) -> Result<(), PgmqError> {
+        self.queue
+            .set_vt::<serde_json::Value>(&self.queue_name, self.message.msg_id, 0)
+            .await?;
+        Ok(())
+    }
+
+    /// Release the message without acknowledging it, see [`MessageGuard`].
+    pub fn into_inner(self) -> Message<T> {
+        self.message
+    }
+}
+
+impl<T> std::ops::Deref for MessageGuard<T> {
+    type Target = Message<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.message
+    }
+}
+
+/// Shared between clones of a [`PGMQueueExt`], see [`PGMQueueExt::stats`].
+#[derive(Debug, Default)]
+struct ClientCounters {
+    sent: AtomicU64,
+    read: AtomicU64,
+    deleted: AtomicU64,
+    archived: AtomicU64,
+}
+
+impl ClientCounters {
+    fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
 }
 
+#[derive(Clone, Debug)]
 pub struct PGMQueueMeta {
     pub queue_name: String,
     pub created_at: chrono::DateTime<Utc>,
@@ -30,16 +156,283 @@ impl PGMQueueExt {
         Ok(Self {
             connection: connect(&url, max_connections).await?,
             url,
+            counters: Arc::default(),
+            poll_permits: None,
+            max_message_size: None,
+            poll_statement_timeout_margin: None,
+            slow_query_threshold: None,
+            clock: Arc::new(SystemClock),
         })
     }
 
+    /// Initialize a connection to PGMQ/Postgres configured from the environment.
+    ///
+    /// Reads the url from [`DATABASE_URL_ENV`] (`DATABASE_URL`), which is required, and the
+    /// pool size from [`MAX_CONNECTIONS_ENV`] (`PGMQ_MAX_CONNECTIONS`), which defaults to
+    /// [`MAX_CONNECTIONS_DEFAULT`]. Fails with [`PgmqError::EnvironmentError`] listing every
+    /// missing or invalid variable.
+    pub async fn from_env() -> Result<Self, PgmqError> {
+        let (url, max_connections) = env_config(|name| std::env::var(name).ok())?;
+        Self::new(url, max_connections).await
+    }
+
+    /// Initialize a connection to PGMQ/Postgres through the Unix socket in `socket_dir`,
+    /// e.g. `/var/run/postgresql`.
+    ///
+    /// Equivalent to [`PGMQueueExt::new`] with a url like
+    /// `postgres:///dbname?host=/var/run/postgresql&user=postgres`, which `new` accepts too.
+    pub async fn new_with_socket(
+        socket_dir: &std::path::Path,
+        dbname: &str,
+        user: &str,
+        max_connections: u32,
+    ) -> Result<Self, PgmqError> {
+        Self::new(socket_url(socket_dir, dbname, user), max_connections).await
+    }
+
     /// BYOP  - bring your own pool
     /// initialize a PGMQ connection with your own SQLx Postgres connection pool
     pub async fn new_with_pool(pool: Pool<Postgres>) -> Self {
         Self {
             url: "".to_owned(),
             connection: pool,
+            counters: Arc::default(),
+            poll_permits: None,
+            max_message_size: None,
+            poll_statement_timeout_margin: None,
+            slow_query_threshold: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Limit the number of concurrent long-polls made through this client and its clones.
+    ///
+    /// Applies to [`PGMQueueExt::read_batch_with_poll`] and
+    /// [`PGMQueueExt::read_batch_with_poll_conditional`]. Each poll holds a pool connection
+    /// for up to its whole timeout, so many idle consumers can otherwise take every
+    /// connection and starve sends. Polls beyond the limit wait for a running poll to
+    /// finish. By default there is no limit; the `_with_cxn` variants are never limited.
+    pub fn with_max_concurrent_polls(mut self, limit: usize) -> Self {
+        self.poll_permits = Some(Arc::new(Semaphore::new(limit)));
+        self
+    }
+
+    /// Reject messages whose serialized JSON is larger than `limit` bytes.
+    ///
+    /// Sends of a larger message fail with [`PgmqError::MessageTooLarge`] before anything
+    /// reaches the database. By default there is no limit. Checking the size of a typed
+    /// message serializes it once more, which is skipped when no limit is set.
+    pub fn with_max_message_size(mut self, limit: usize) -> Self {
+        self.max_message_size = Some(limit);
+        self
+    }
+
+    /// Cap each long-poll with a `statement_timeout` of its poll timeout plus `margin`.
+    ///
+    /// Applies to [`PGMQueueExt::read_batch_with_poll`] and
+    /// [`PGMQueueExt::read_batch_with_poll_conditional`], which then run in a transaction
+    /// with `SET LOCAL statement_timeout`. A poll that is still running once the timeout
+    /// expires is cancelled by the server and fails with [`PgmqError::Timeout`], instead of
+    /// holding its connection indefinitely. Off by default, since the extra transaction costs
+    /// round trips on every poll. The `_with_cxn` variants never set a timeout.
+    pub fn with_poll_statement_timeout_margin(
+        mut self,
+        margin: Option<std::time::Duration>,
+    ) -> Self {
+        self.poll_statement_timeout_margin = margin;
+        self
+    }
+
+    /// Log a warning for each operation that takes longer than `threshold`, with the
+    /// operation, queue and elapsed time.
+    ///
+    /// Applies to `send`, `send_delay`, `read`, `pop`, `set_vt`, `delete`, `delete_batch`,
+    /// `archive`, `archive_batch` and `purge_queue`, timed from the call until the result is
+    /// returned, including waiting for a pool connection. Long-polls are not timed, as they
+    /// wait for messages by design, and neither are the `_with_cxn` variants. Disabled by
+    /// default.
+    pub fn with_slow_query_threshold(mut self, threshold: Option<std::time::Duration>) -> Self {
+        self.slow_query_threshold = threshold;
+        self
+    }
+
+    /// Awaits `operation` on `queue_name`, logging it if it is slower than the
+    /// [`PGMQueueExt::with_slow_query_threshold`].
+    async fn timed<R>(
+        &self,
+        operation: &str,
+        queue_name: &(impl AsQueueName + ?Sized),
+        fut: impl std::future::Future<Output = R>,
+    ) -> R {
+        let Some(threshold) = self.slow_query_threshold else {
+            return fut.await;
+        };
+        let start = std::time::Instant::now();
+        let result = fut.await;
+        let elapsed = start.elapsed();
+        if elapsed > threshold {
+            let queue_name = queue_name.as_queue_name().unwrap_or("<invalid>");
+            warn!("slow operation: {operation} on queue {queue_name} took {elapsed:?}");
+        }
+        result
+    }
+
+    /// Replace the clock used for time-dependent behaviour such as
+    /// [`PGMQueueExt::message_age`], e.g. with a `FixedClock` for deterministic tests.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// How long `message` has been in the queue, according to this client's clock.
+    ///
+    /// Same as [`Message::age`] unless the clock was replaced with `with_clock`.
+    pub fn message_age<T>(&self, message: &Message<T>) -> chrono::Duration {
+        message.age_at(self.clock.now())
+    }
+
+    /// Starts the transaction a poll of `poll_timeout` runs in, with its `statement_timeout`
+    /// already set. `None` if no timeout is configured, see
+    /// [`PGMQueueExt::with_poll_statement_timeout_margin`].
+    async fn begin_poll(
+        &self,
+        poll_timeout: Option<std::time::Duration>,
+    ) -> Result<Option<(sqlx::Transaction<'static, Postgres>, std::time::Duration)>, PgmqError>
+    {
+        let Some(margin) = self.poll_statement_timeout_margin else {
+            return Ok(None);
+        };
+        let poll_timeout_s = poll_timeout.map_or(DEFAULT_POLL_TIMEOUT_S as u64, |t| t.as_secs());
+        let timeout = std::time::Duration::from_secs(poll_timeout_s) + margin;
+        let mut tx = self.connection.begin().await?;
+        // SET does not take bind parameters
+        sqlx::query(&format!(
+            "SET LOCAL statement_timeout = {};",
+            timeout.as_millis()
+        ))
+        .execute(&mut *tx)
+        .await?;
+        Ok(Some((tx, timeout)))
+    }
+
+    fn check_message_len(&self, size: usize) -> Result<(), PgmqError> {
+        match self.max_message_size {
+            Some(limit) if size > limit => Err(PgmqError::MessageTooLarge { size, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_message_size(&self, message: &serde_json::Value) -> Result<(), PgmqError> {
+        if self.max_message_size.is_some() {
+            self.check_message_len(serde_json::to_vec(message)?.len())?;
+        }
+        Ok(())
+    }
+
+    /// Number of polls that can start right now without waiting, `None` if polls are not limited.
+    pub fn available_polls(&self) -> Option<usize> {
+        self.poll_permits
+            .as_ref()
+            .map(|permits| permits.available_permits())
+    }
+
+    async fn poll_permit(&self) -> Result<Option<SemaphorePermit<'_>>, PgmqError> {
+        match &self.poll_permits {
+            Some(permits) => Ok(Some(
+                permits
+                    .acquire()
+                    .await
+                    .map_err(|e| PgmqError::Other(e.to_string()))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// The connection pool used by this client, for running custom SQL against the same database.
+    pub fn pool(&self) -> &Pool<Postgres> {
+        &self.connection
+    }
+
+    /// Counts of messages sent, read, deleted and archived through this client and its clones.
+    pub fn stats(&self) -> PgmqClientStats {
+        PgmqClientStats {
+            sent: self.counters.sent.load(Ordering::Relaxed),
+            read: self.counters.read.load(Ordering::Relaxed),
+            deleted: self.counters.deleted.load(Ordering::Relaxed),
+            archived: self.counters.archived.load(Ordering::Relaxed),
+        }
+    }
+
+    pub async fn health_check_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        executor: E,
+    ) -> Result<(), PgmqError> {
+        sqlx::query("SELECT 1;").execute(executor).await?;
+        Ok(())
+    }
+
+    /// Check that a connection can be acquired and a trivial query run on it.
+    pub async fn health_check(&self) -> Result<(), PgmqError> {
+        self.health_check_with_cxn(&self.connection).await
+    }
+
+    /// Same as [`PGMQueueExt::health_check`], but gives up after `timeout`.
+    ///
+    /// Returns [`PgmqError::Timeout`] if the check, including waiting for a free connection
+    /// in the pool, does not complete in time. Suitable for readiness probes, which need a
+    /// bounded response even when the database or the pool is stuck.
+    pub async fn health_check_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<(), PgmqError> {
+        tokio::time::timeout(timeout, self.health_check())
+            .await
+            .map_err(|_| PgmqError::Timeout { timeout })?
+    }
+
+    pub async fn call_function_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        name: &str,
+        args: &[PgmqArg],
+        executor: E,
+    ) -> Result<Vec<PgRow>, PgmqError> {
+        let valid = !name.is_empty() && name.bytes().all(|c| c.is_ascii_lowercase() || c == b'_');
+        if !valid {
+            return Err(PgmqError::InvalidFunctionName {
+                name: name.to_owned(),
+            });
         }
+        let placeholders = (1..=args.len())
+            .map(|i| format!("${i}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!("SELECT * FROM pgmq.{name}({placeholders});");
+        let mut query = sqlx::query(&sql);
+        for arg in args {
+            query = match arg {
+                PgmqArg::Text(v) => query.bind(v),
+                PgmqArg::Integer(v) => query.bind(v),
+                PgmqArg::BigInt(v) => query.bind(v),
+                PgmqArg::BigIntArray(v) => query.bind(v),
+                PgmqArg::Boolean(v) => query.bind(v),
+                PgmqArg::Json(v) => query.bind(v),
+            };
+        }
+        Ok(query.fetch_all(executor).await?)
+    }
+
+    /// Call a function in the `pgmq` schema that this crate does not wrap.
+    ///
+    /// `name` must only contain lowercase letters and underscores, and is always resolved in
+    /// the `pgmq` schema. Arguments are passed as bind parameters, never interpolated.
+    pub async fn call_function(
+        &self,
+        name: &str,
+        args: &[PgmqArg],
+    ) -> Result<Vec<PgRow>, PgmqError> {
+        self.call_function_with_cxn(name, args, &self.connection)
+            .await
     }
 
     #[cfg(feature = "cli")]
@@ -56,6 +449,12 @@ impl PGMQueueExt {
         self.install_sql_with_cxn(&self.connection, version).await
     }
 
+    /// Install PGMQ into a database that does not have it yet, erroring if it is already present.
+    #[cfg(feature = "cli")]
+    pub async fn install_sql_fresh(&self, version: Option<&String>) -> Result<(), PgmqError> {
+        install_pgmq_fresh(&self.connection, version).await
+    }
+
     pub async fn init_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
         &self,
         executor: E,
@@ -71,6 +470,113 @@ impl PGMQueueExt {
         self.init_with_cxn(&self.connection).await
     }
 
+    pub async fn upgrade_with_cxn<'c, E>(&self, executor: E) -> Result<Vec<String>, PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        let mut tx = executor.begin().await?;
+        let installed = sqlx::query_scalar::<_, String>(
+            "SELECT extversion FROM pg_extension WHERE extname = 'pgmq';",
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let installed = match installed {
+            Some(installed) => installed,
+            None => {
+                let schema =
+                    sqlx::query_scalar::<_, bool>("SELECT to_regnamespace('pgmq') IS NOT NULL;")
+                        .fetch_one(&mut *tx)
+                        .await?;
+                return Err(PgmqError::InstallationError(if schema {
+                    "pgmq was installed from SQL scripts rather than as an extension, it cannot be upgraded with ALTER EXTENSION".to_owned()
+                } else {
+                    "pgmq is not installed, it must be installed before it can be upgraded"
+                        .to_owned()
+                }));
+            }
+        };
+        let available = sqlx::query_scalar::<_, String>(
+            "SELECT default_version FROM pg_available_extensions WHERE name = 'pgmq';",
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            PgmqError::InstallationError(
+                "pgmq is installed but no longer available on the server, it cannot be upgraded"
+                    .to_owned(),
+            )
+        })?;
+        if installed == available {
+            return Ok(vec![]);
+        }
+        let path = sqlx::query_scalar::<_, String>(
+            "SELECT path FROM pg_extension_update_paths('pgmq') WHERE source = $1 AND target = $2 AND path IS NOT NULL;",
+        )
+        .bind(&installed)
+        .bind(&available)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            PgmqError::InstallationError(format!(
+                "no upgrade path for pgmq from {installed} to {available}"
+            ))
+        })?;
+        sqlx::query("ALTER EXTENSION pgmq UPDATE;")
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(path.split("--").map(str::to_owned).collect())
+    }
+
+    /// Upgrade an installed pgmq extension to the newest version available on the server.
+    ///
+    /// Errors if the extension is not installed yet, use [`init`](Self::init) for that, or if
+    /// pgmq was installed from SQL scripts, which `ALTER EXTENSION` cannot upgrade. Returns the
+    /// versions the extension went through, from the installed one to the new one, which is
+    /// empty when the extension is already up to date.
+    pub async fn upgrade(&self) -> Result<Vec<String>, PgmqError> {
+        self.upgrade_with_cxn(&self.connection).await
+    }
+
+    pub async fn verify_install_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        executor: E,
+    ) -> Result<(), PgmqError> {
+        let missing = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT name FROM (VALUES
+                ('schema pgmq', to_regnamespace('pgmq') IS NOT NULL),
+                ('table pgmq.meta', to_regclass('pgmq.meta') IS NOT NULL),
+                ('function pgmq.send', EXISTS(
+                    SELECT 1 FROM pg_proc p
+                    JOIN pg_namespace n ON n.oid = p.pronamespace
+                    WHERE n.nspname = 'pgmq' AND p.proname = 'send'
+                ))
+            ) AS checks(name, present)
+            WHERE NOT present;
+            "#,
+        )
+        .fetch_all(executor)
+        .await?;
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(PgmqError::InstallationError(format!(
+                "pgmq installation is incomplete, missing: {}",
+                missing.join(", ")
+            )))
+        }
+    }
+
+    /// Check that the objects PGMQ relies on are present in the database.
+    ///
+    /// Returns an [`PgmqError::InstallationError`] listing every missing object.
+    /// Intended as a smoke test after [`init`](Self::init) or `install_sql`.
+    pub async fn verify_install(&self) -> Result<(), PgmqError> {
+        self.verify_install_with_cxn(&self.connection).await
+    }
+
     pub async fn create_with_cxn<'c, E>(
         &self,
         queue_name: &str,
@@ -124,6 +630,39 @@ impl PGMQueueExt {
         Ok(true)
     }
 
+    pub async fn create_unlogged_or_get_with_cxn<'c, E>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<PGMQueueMeta, PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        let mut conn = executor.acquire().await?;
+        // pgmq.create_unlogged is a no-op when the queue already exists
+        self.create_unlogged_with_cxn(queue_name, &mut *conn)
+            .await?;
+        let row = sqlx::query(
+            "SELECT queue_name, is_partitioned, is_unlogged, created_at FROM pgmq.meta WHERE queue_name = $1::text;",
+        )
+        .bind(queue_name)
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(queue_meta_from_row(&row)?)
+    }
+
+    /// Create an unlogged queue if it does not exist yet, and return its metadata either way.
+    ///
+    /// If a queue with this name already exists, its metadata is returned as-is, even if it is
+    /// not an unlogged queue.
+    pub async fn create_unlogged_or_get(
+        &self,
+        queue_name: &str,
+    ) -> Result<PGMQueueMeta, PgmqError> {
+        self.create_unlogged_or_get_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
     pub async fn create_partitioned_with_cxn<
         'c,
         E: sqlx::Executor<'c, Database = Postgres> + std::marker::Copy,
@@ -160,6 +699,86 @@ impl PGMQueueExt {
             .await
     }
 
+    pub async fn ensure_queues_with_cxn<'c, E>(
+        &self,
+        specs: &[QueueSpec],
+        executor: E,
+    ) -> Result<Vec<(String, bool)>, PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        for spec in specs {
+            check_input(&spec.name)?;
+        }
+        let mut tx = executor.begin().await?;
+        let mut created = Vec::with_capacity(specs.len());
+        for spec in specs {
+            let exists = sqlx::query_scalar::<_, bool>(
+                "SELECT EXISTS(SELECT 1 FROM pgmq.meta WHERE queue_name = $1::text);",
+            )
+            .bind(&spec.name)
+            .fetch_one(&mut *tx)
+            .await?;
+            if exists {
+                created.push((spec.name.clone(), false));
+                continue;
+            }
+            match spec.kind {
+                QueueType::Standard => {
+                    sqlx::query("SELECT * from pgmq.create(queue_name=>$1::text);")
+                        .bind(&spec.name)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                QueueType::Unlogged => {
+                    sqlx::query("SELECT * from pgmq.create_unlogged(queue_name=>$1::text);")
+                        .bind(&spec.name)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                QueueType::Partitioned => {
+                    // leave out unset intervals so the extension defaults apply
+                    let mut args = vec!["queue_name=>$1::text".to_owned()];
+                    let mut intervals = vec![];
+                    for (arg, value) in [
+                        ("partition_interval", &spec.partition_interval),
+                        ("retention_interval", &spec.retention),
+                    ] {
+                        if let Some(value) = value {
+                            intervals.push(value);
+                            args.push(format!("{arg}=>${}::text", intervals.len() + 1));
+                        }
+                    }
+                    let stmt = format!(
+                        "SELECT * from pgmq.create_partitioned({});",
+                        args.join(", ")
+                    );
+                    let mut query = sqlx::query(&stmt).bind(&spec.name);
+                    for value in intervals {
+                        query = query.bind(value);
+                    }
+                    query.execute(&mut *tx).await?;
+                }
+            }
+            created.push((spec.name.clone(), true));
+        }
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    /// Create every queue in `specs` that does not exist yet, in a single transaction.
+    ///
+    /// All names are validated before anything is created, and if creating any queue fails
+    /// none of them are. Returns each queue name with whether it was newly created, in the
+    /// order of `specs`. Existing queues are left as they are, even if they are of a different
+    /// [`QueueType`]. `partition_interval` and `retention` only apply to partitioned queues.
+    pub async fn ensure_queues(
+        &self,
+        specs: &[QueueSpec],
+    ) -> Result<Vec<(String, bool)>, PgmqError> {
+        self.ensure_queues_with_cxn(specs, &self.connection).await
+    }
+
     pub async fn drop_queue_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
         &self,
         queue_name: &str,
@@ -181,6 +800,161 @@ impl PGMQueueExt {
         self.drop_queue_with_cxn(queue_name, &self.connection).await
     }
 
+    pub async fn swap_queues_with_cxn<'c, E>(
+        &self,
+        a: &str,
+        b: &str,
+        executor: E,
+    ) -> Result<(), PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        check_input(a)?;
+        check_input(b)?;
+        let incompatible = |reason: &str| PgmqError::IncompatibleQueues {
+            a: a.to_owned(),
+            b: b.to_owned(),
+            reason: reason.to_owned(),
+        };
+        let mut tx = executor.begin().await?;
+        // run swaps one at a time, so two swaps sharing a queue cannot deadlock
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('pgmq.swap_queues'));")
+            .execute(&mut *tx)
+            .await?;
+        let rows = sqlx::query(
+            "SELECT queue_name, is_partitioned, is_unlogged, created_at FROM pgmq.meta WHERE queue_name IN ($1::text, $2::text) FOR UPDATE;",
+        )
+        .bind(a)
+        .bind(b)
+        .fetch_all(&mut *tx)
+        .await?;
+        let metas = rows
+            .iter()
+            .map(queue_meta_from_row)
+            .collect::<Result<Vec<_>, _>>()?;
+        let find = |name: &str| {
+            metas
+                .iter()
+                .find(|m| m.queue_name == name)
+                .ok_or_else(|| PgmqError::QueueNotFound {
+                    name: name.to_owned(),
+                })
+        };
+        let (meta_a, meta_b) = (find(a)?, find(b)?);
+        if a == b {
+            // swapping a queue with itself only needs it to exist
+            tx.commit().await?;
+            return Ok(());
+        }
+        if meta_a.is_partitioned || meta_b.is_partitioned {
+            // pg_partman's configuration refers to the tables by name
+            return Err(incompatible("partitioned queues cannot be swapped"));
+        }
+        if meta_a.is_unlogged != meta_b.is_unlogged {
+            return Err(incompatible("only one of the queues is unlogged"));
+        }
+        let txid: i64 = sqlx::query_scalar("SELECT txid_current();")
+            .fetch_one(&mut *tx)
+            .await?;
+        let tmp = format!("pgmq_swap_{txid}");
+        let taken: bool = sqlx::query_scalar(
+            "SELECT to_regclass(format('pgmq.%I', $1::text)) IS NOT NULL OR to_regclass(format('pgmq.%I', $2::text)) IS NOT NULL;",
+        )
+        .bind(format!("{QUEUE_PREFIX}_{tmp}"))
+        .bind(format!("{ARCHIVE_PREFIX}_{tmp}"))
+        .fetch_one(&mut *tx)
+        .await?;
+        if taken {
+            return Err(incompatible(&format!(
+                "the temporary name '{tmp}' is already in use"
+            )));
+        }
+        rename_queue_relations(&mut tx, a, &tmp).await?;
+        rename_queue_relations(&mut tx, b, a).await?;
+        rename_queue_relations(&mut tx, &tmp, b).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Swap the contents of two queues, e.g. to promote a freshly filled queue in place of a
+    /// live one.
+    ///
+    /// The queue and archive tables are renamed, together with their indexes and id
+    /// sequences, in a single transaction, so readers see either the old or the new
+    /// assignment. Both queues must exist and be of the same type; partitioned queues cannot
+    /// be swapped and return [`PgmqError::IncompatibleQueues`]. Swapping an existing queue
+    /// with itself does nothing.
+    pub async fn swap_queues(&self, a: &str, b: &str) -> Result<(), PgmqError> {
+        self.swap_queues_with_cxn(a, b, &self.connection).await
+    }
+
+    pub async fn enable_notify_insert_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        throttle: std::time::Duration,
+        executor: E,
+    ) -> Result<(), PgmqError> {
+        check_input(queue_name)?;
+        let throttle_ms = i32::try_from(throttle.as_millis()).unwrap_or(i32::MAX);
+        sqlx::query(
+            "SELECT pgmq.enable_notify_insert(queue_name=>$1::text, throttle_interval_ms=>$2::integer);",
+        )
+        .bind(queue_name)
+        .bind(throttle_ms)
+        .execute(executor)
+        .await
+        .map_err(notify_insert_unavailable)?;
+        Ok(())
+    }
+
+    /// Send a notification on [`PGMQueueExt::notify_insert_channel`] when messages are
+    /// inserted into a queue, at most once per `throttle`.
+    ///
+    /// Notifications are delivered by Postgres when the inserting transaction commits, never
+    /// before, and not at all if it rolls back. A message sent with
+    /// [`PGMQueueExt::send_with_cxn`] inside a transaction therefore wakes listeners only once
+    /// it can be read. With a zero `throttle` every insert notifies, though Postgres folds
+    /// the notifications of one transaction into one. Requires pgmq >= `1.8.0`.
+    pub async fn enable_notify_insert(
+        &self,
+        queue_name: &str,
+        throttle: std::time::Duration,
+    ) -> Result<(), PgmqError> {
+        self.enable_notify_insert_with_cxn(queue_name, throttle, &self.connection)
+            .await
+    }
+
+    pub async fn disable_notify_insert_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<(), PgmqError> {
+        check_input(queue_name)?;
+        sqlx::query("SELECT pgmq.disable_notify_insert(queue_name=>$1::text);")
+            .bind(queue_name)
+            .execute(executor)
+            .await
+            .map_err(notify_insert_unavailable)?;
+        Ok(())
+    }
+
+    /// Stop the notifications enabled with [`PGMQueueExt::enable_notify_insert`].
+    pub async fn disable_notify_insert(&self, queue_name: &str) -> Result<(), PgmqError> {
+        self.disable_notify_insert_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    /// Channel that [`PGMQueueExt::enable_notify_insert`] notifies on, for use with
+    /// `sqlx::postgres::PgListener::listen`.
+    pub fn notify_insert_channel(queue_name: &str) -> Result<String, PgmqError> {
+        check_input(queue_name)?;
+        // the trigger names the channel after the table, whose name is lowercased
+        Ok(format!(
+            "{PGMQ_SCHEMA}.{QUEUE_PREFIX}_{}.INSERT",
+            queue_name.to_lowercase()
+        ))
+    }
+
     /// Drop an existing queue table.
     pub async fn purge_queue_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
         &self,
@@ -197,7 +971,86 @@ impl PGMQueueExt {
 
     /// Drop an existing queue table.
     pub async fn purge_queue(&self, queue_name: &str) -> Result<i64, PgmqError> {
-        self.purge_queue_with_cxn(queue_name, &self.connection)
+        self.timed(
+            "purge_queue",
+            queue_name,
+            self.purge_queue_with_cxn(queue_name, &self.connection),
+        )
+        .await
+    }
+
+    pub async fn purge_queue_returning_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<Vec<i64>, PgmqError> {
+        check_input(queue_name)?;
+        let mut purged: Vec<i64> = sqlx::query_scalar(&format!(
+            "DELETE FROM pgmq.{QUEUE_PREFIX}_{queue_name} RETURNING msg_id;"
+        ))
+        .fetch_all(executor)
+        .await?;
+        purged.sort_unstable();
+        Ok(purged)
+    }
+
+    /// Delete every message in a queue, returning the ids of the deleted messages in ascending
+    /// order.
+    ///
+    /// Unlike [`purge_queue`](Self::purge_queue) this deletes row by row rather than truncating,
+    /// and holds all ids in memory, so it is slower on large queues.
+    pub async fn purge_queue_returning(&self, queue_name: &str) -> Result<Vec<i64>, PgmqError> {
+        self.purge_queue_returning_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    pub async fn delete_where_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        filter: MessageFilter,
+        executor: E,
+    ) -> Result<Vec<i64>, PgmqError> {
+        check_input(queue_name)?;
+        let sql = format!(
+            "DELETE FROM pgmq.{QUEUE_PREFIX}_{queue_name} WHERE {} RETURNING msg_id;",
+            filter.predicate()
+        );
+        let query = sqlx::query_scalar(&sql);
+        let query = match filter {
+            MessageFilter::ReadCtGreaterThan(read_ct) => query.bind(read_ct),
+            MessageFilter::EnqueuedBefore(at) | MessageFilter::LastReadBefore(at) => query.bind(at),
+            MessageFilter::MsgIdBelow(msg_id) => query.bind(msg_id),
+        };
+        let mut deleted: Vec<i64> = query
+            .fetch_all(executor)
+            .await
+            .map_err(|e| queue_not_found(queue_name, e))?;
+        deleted.sort_unstable();
+        ClientCounters::add(&self.counters.deleted, deleted.len() as u64);
+        Ok(deleted)
+    }
+
+    /// Delete the messages of a queue matching `filter`, returning their ids in ascending
+    /// order.
+    ///
+    /// Visible and in-flight messages are deleted alike.
+    pub async fn delete_where(
+        &self,
+        queue_name: &str,
+        filter: MessageFilter,
+    ) -> Result<Vec<i64>, PgmqError> {
+        self.delete_where_with_cxn(queue_name, filter, &self.connection)
+            .await
+    }
+
+    /// Delete the messages that were read more than `threshold` times, e.g. poison messages
+    /// that consumers keep failing on. Returns their ids in ascending order.
+    pub async fn delete_where_read_ct_gt(
+        &self,
+        queue_name: &str,
+        threshold: i32,
+    ) -> Result<Vec<i64>, PgmqError> {
+        self.delete_where(queue_name, MessageFilter::ReadCtGreaterThan(threshold))
             .await
     }
 
@@ -213,14 +1066,7 @@ impl PGMQueueExt {
         } else {
             let queues = queues
                 .into_iter()
-                .map(|q| {
-                    Ok(PGMQueueMeta {
-                        queue_name: q.try_get("queue_name")?,
-                        created_at: q.try_get("created_at")?,
-                        is_unlogged: q.try_get("is_unlogged")?,
-                        is_partitioned: q.try_get("is_partitioned")?,
-                    })
-                })
+                .map(|q| queue_meta_from_row(&q))
                 .collect::<Result<_, sqlx::Error>>()?;
             Ok(Some(queues))
         }
@@ -231,13 +1077,347 @@ impl PGMQueueExt {
         self.list_queues_with_cxn(&self.connection).await
     }
 
-    pub async fn set_vt_with_cxn<
+    pub async fn list_queues_approx_counts_with_cxn<
         'c,
         E: sqlx::Executor<'c, Database = Postgres>,
-        T: for<'de> Deserialize<'de>,
     >(
         &self,
-        queue_name: &str,
+        executor: E,
+    ) -> Result<Vec<(PGMQueueMeta, i64)>, PgmqError> {
+        let rows = sqlx::query(&format!(
+            r#"
+            WITH queues AS (
+                SELECT m.*, to_regclass(format('{PGMQ_SCHEMA}.%I', lower('{QUEUE_PREFIX}_' || m.queue_name))) AS relid
+                FROM {PGMQ_SCHEMA}.meta m
+            )
+            SELECT
+                q.queue_name, q.is_partitioned, q.is_unlogged, q.created_at,
+                COALESCE((
+                    SELECT sum(GREATEST(c.reltuples, 0))
+                    FROM pg_class c
+                    WHERE c.oid = q.relid
+                       OR c.oid IN (SELECT inhrelid FROM pg_inherits WHERE inhparent = q.relid)
+                ), 0)::bigint AS approx_count
+            FROM queues q
+            ORDER BY q.queue_name;
+            "#
+        ))
+        .fetch_all(executor)
+        .await?;
+        rows.iter()
+            .map(|row| Ok((queue_meta_from_row(row)?, row.try_get("approx_count")?)))
+            .collect()
+    }
+
+    /// List all queues with an estimate of the number of messages in each.
+    ///
+    /// The estimates are the planner statistics in `pg_class.reltuples`, as of the last
+    /// `VACUUM` or `ANALYZE` of each queue table, so they can be far off on busy queues and
+    /// are 0 for tables that were never analyzed. In exchange the query reads one catalog row
+    /// per queue table, rather than counting every message like
+    /// [`PGMQueueExt::consumer_lag`]. Partitioned queues sum the estimates of their
+    /// partitions.
+    pub async fn list_queues_approx_counts(&self) -> Result<Vec<(PGMQueueMeta, i64)>, PgmqError> {
+        self.list_queues_approx_counts_with_cxn(&self.connection)
+            .await
+    }
+
+    pub async fn list_queues_created_before_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+    >(
+        &self,
+        before: chrono::DateTime<Utc>,
+        executor: E,
+    ) -> Result<Vec<PGMQueueMeta>, PgmqError> {
+        let queues = sqlx::query(
+            r#"SELECT queue_name, is_partitioned, is_unlogged, created_at from pgmq.list_queues() WHERE created_at < $1 ORDER BY created_at;"#,
+        )
+        .bind(before)
+        .fetch_all(executor)
+        .await?;
+        Ok(queues
+            .iter()
+            .map(queue_meta_from_row)
+            .collect::<Result<_, sqlx::Error>>()?)
+    }
+
+    /// List the queues created before `before`, oldest first.
+    ///
+    /// The filter runs in Postgres, so finding stale queues does not require fetching every queue.
+    pub async fn list_queues_created_before(
+        &self,
+        before: chrono::DateTime<Utc>,
+    ) -> Result<Vec<PGMQueueMeta>, PgmqError> {
+        self.list_queues_created_before_with_cxn(before, &self.connection)
+            .await
+    }
+
+    pub async fn queue_meta_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<Option<PGMQueueMeta>, PgmqError> {
+        check_input(queue_name)?;
+        let row = sqlx::query(
+            "SELECT queue_name, is_partitioned, is_unlogged, created_at FROM pgmq.list_queues() WHERE queue_name = $1::text;",
+        )
+        .bind(queue_name)
+        .fetch_optional(executor)
+        .await?;
+        Ok(row.as_ref().map(queue_meta_from_row).transpose()?)
+    }
+
+    /// Metadata of a single queue, or `None` if the queue does not exist.
+    pub async fn queue_meta(&self, queue_name: &str) -> Result<Option<PGMQueueMeta>, PgmqError> {
+        self.queue_meta_with_cxn(queue_name, &self.connection).await
+    }
+
+    pub async fn list_partitions_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<Vec<PartitionInfo>, PgmqError> {
+        check_input(queue_name)?;
+        // bounds look like `FOR VALUES FROM ('a') TO ('b')`, or `DEFAULT`
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                name,
+                btrim(substring(bound FROM 'FROM \((.*)\) TO'), chr(39)) AS range_start,
+                btrim(substring(bound FROM 'TO \((.*)\)$'), chr(39)) AS range_end
+            FROM (
+                SELECT c.relname::text AS name, pg_get_expr(c.relpartbound, c.oid) AS bound
+                FROM pg_inherits i
+                JOIN pg_class c ON c.oid = i.inhrelid
+                WHERE i.inhparent = to_regclass($1::text)
+            ) partitions
+            ORDER BY name
+            "#,
+        )
+        .bind(format!("pgmq.{QUEUE_PREFIX}_{queue_name}"))
+        .fetch_all(executor)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(PartitionInfo {
+                    name: row.try_get("name")?,
+                    range_start: row.try_get("range_start")?,
+                    range_end: row.try_get("range_end")?,
+                })
+            })
+            .collect()
+    }
+
+    /// The partitions of a partitioned queue, ordered by name, with their range bounds.
+    ///
+    /// Returns an empty list for queues that are not partitioned.
+    pub async fn list_partitions(&self, queue_name: &str) -> Result<Vec<PartitionInfo>, PgmqError> {
+        self.list_partitions_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    pub async fn drop_partition_with_cxn<'c, E>(
+        &self,
+        queue_name: &str,
+        partition_name: &str,
+        executor: E,
+    ) -> Result<(), PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        check_input(queue_name)?;
+        let mut tx = executor.begin().await?;
+        // the qualified name is quoted by Postgres, so it is safe to interpolate below
+        let qualified: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT format('%I.%I', n.nspname, c.relname)
+            FROM pg_inherits i
+            JOIN pg_class c ON c.oid = i.inhrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE i.inhparent = to_regclass($1::text) AND c.relname = $2::text
+            "#,
+        )
+        .bind(format!("pgmq.{QUEUE_PREFIX}_{queue_name}"))
+        .bind(partition_name)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(qualified) = qualified else {
+            return Err(PgmqError::UnknownPartition {
+                queue_name: queue_name.to_owned(),
+                partition_name: partition_name.to_owned(),
+            });
+        };
+        sqlx::query(&format!("DROP TABLE {qualified};"))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Drop one partition of a partitioned queue, e.g. after dumping it to cold storage.
+    ///
+    /// Returns [`PgmqError::UnknownPartition`] without dropping anything if `partition_name`
+    /// is not a partition of the queue's table. The messages in the partition are deleted,
+    /// they are not archived.
+    pub async fn drop_partition(
+        &self,
+        queue_name: &str,
+        partition_name: &str,
+    ) -> Result<(), PgmqError> {
+        self.drop_partition_with_cxn(queue_name, partition_name, &self.connection)
+            .await
+    }
+
+    pub async fn queue_size_bytes_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<QueueSize, PgmqError> {
+        check_input(queue_name)?;
+        let queue_table = format!("pgmq.{QUEUE_PREFIX}_{queue_name}");
+        let archive_table = format!("pgmq.{ARCHIVE_PREFIX}_{queue_name}");
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT
+                pg_total_relation_size('{queue_table}'::regclass) AS table_bytes,
+                pg_total_relation_size('{archive_table}'::regclass) AS archive_bytes,
+                (SELECT avg(pg_column_size(message))::float8 FROM {queue_table}) AS avg_message_bytes
+            "#
+        ))
+        .fetch_one(executor)
+        .await?;
+        Ok(QueueSize {
+            table_bytes: row.try_get("table_bytes")?,
+            archive_bytes: row.try_get("archive_bytes")?,
+            avg_message_bytes: row.try_get("avg_message_bytes")?,
+        })
+    }
+
+    /// Approximate storage used by a queue and its archive, for capacity planning.
+    ///
+    /// Sizes come from `pg_total_relation_size` and so include indexes and TOAST. The average
+    /// message size is the stored (possibly compressed) size of the `message` column.
+    pub async fn queue_size_bytes(&self, queue_name: &str) -> Result<QueueSize, PgmqError> {
+        self.queue_size_bytes_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    pub async fn count_by_json_field_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        field: &str,
+        executor: E,
+    ) -> Result<Vec<(String, i64)>, PgmqError> {
+        check_input(queue_name)?;
+        check_json_key(field)?;
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT message->>$1::text AS value, count(*) AS count
+            FROM pgmq.{QUEUE_PREFIX}_{queue_name}
+            WHERE message->>$1::text IS NOT NULL
+            GROUP BY 1
+            ORDER BY 1
+            "#
+        ))
+        .bind(field)
+        .fetch_all(executor)
+        .await?;
+        rows.iter()
+            .map(|row| Ok((row.try_get("value")?, row.try_get("count")?)))
+            .collect()
+    }
+
+    /// Count the messages in a queue grouped by the value of the top-level json `field`.
+    ///
+    /// Values are returned as text, in ascending order. Messages without the field, or where it
+    /// is `null`, are not counted. Both visible and invisible messages are included. The field
+    /// must be alphanumeric or underscores.
+    pub async fn count_by_json_field(
+        &self,
+        queue_name: &str,
+        field: &str,
+    ) -> Result<Vec<(String, i64)>, PgmqError> {
+        self.count_by_json_field_with_cxn(queue_name, field, &self.connection)
+            .await
+    }
+
+    pub async fn consumer_lag_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<ConsumerLag, PgmqError> {
+        check_input(queue_name)?;
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT
+                count(*) FILTER (WHERE vt <= now()) AS visible_count,
+                count(*) FILTER (WHERE vt > now()) AS in_flight_count,
+                min(enqueued_at) FILTER (WHERE vt <= now()) AS oldest_visible_enqueued_at,
+                now() AS now
+            FROM pgmq.{QUEUE_PREFIX}_{queue_name};
+            "#
+        ))
+        .fetch_one(executor)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        let now: chrono::DateTime<Utc> = row.try_get("now")?;
+        let oldest: Option<chrono::DateTime<Utc>> = row.try_get("oldest_visible_enqueued_at")?;
+        Ok(ConsumerLag {
+            visible_count: row.try_get("visible_count")?,
+            oldest_visible_age: oldest.map(|enqueued_at| now - enqueued_at),
+            in_flight_count: row.try_get("in_flight_count")?,
+        })
+    }
+
+    /// Visible and in-flight message counts, and the age of the oldest visible message, read
+    /// from the queue table in a single query.
+    ///
+    /// Ages are measured against the database clock. Delayed messages count as in flight.
+    pub async fn consumer_lag(&self, queue_name: &str) -> Result<ConsumerLag, PgmqError> {
+        self.consumer_lag_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    pub async fn msg_id_range_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<Option<MsgIdRange>, PgmqError> {
+        check_input(queue_name)?;
+        let row = sqlx::query(&format!(
+            "SELECT min(msg_id) AS min_msg_id, max(msg_id) AS max_msg_id, count(*) AS count FROM pgmq.{QUEUE_PREFIX}_{queue_name};"
+        ))
+        .fetch_one(executor)
+        .await?;
+        let count: i64 = row.try_get("count")?;
+        if count == 0 {
+            return Ok(None);
+        }
+        Ok(Some(MsgIdRange {
+            min_msg_id: row.try_get("min_msg_id")?,
+            max_msg_id: row.try_get("max_msg_id")?,
+            count,
+        }))
+    }
+
+    /// The smallest and largest message id in a queue and how many messages it holds,
+    /// `None` if the queue is empty.
+    ///
+    /// Comparing the count to the size of the range, see [`MsgIdRange::gaps`], shows how many
+    /// messages in between were removed. Invisible messages are included.
+    pub async fn msg_id_range(&self, queue_name: &str) -> Result<Option<MsgIdRange>, PgmqError> {
+        self.msg_id_range_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    pub async fn set_vt_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
         msg_id: i64,
         vt: i32,
         executor: E,
@@ -270,81 +1450,718 @@ impl PGMQueueExt {
         msg_id: i64,
         vt: i32,
     ) -> Result<Message<T>, PgmqError> {
-        self.set_vt_with_cxn(queue_name, msg_id, vt, &self.connection)
+        self.timed(
+            "set_vt",
+            queue_name,
+            self.set_vt_with_cxn(queue_name, msg_id, vt, &self.connection),
+        )
+        .await
+    }
+
+    pub async fn set_vt_at_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+        at: chrono::DateTime<Utc>,
+        executor: E,
+    ) -> Result<Message<T>, PgmqError> {
+        check_input(queue_name)?;
+        let updated = sqlx::query(
+            r#"SELECT msg_id, read_ct, enqueued_at, vt, message from pgmq.set_vt(queue_name=>$1::text, msg_id=>$2::bigint, vt=>GREATEST($3::timestamptz, clock_timestamp()));"#
+        )
+        .bind(queue_name)
+        .bind(msg_id)
+        .bind(at)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.code().as_deref() == Some(UNDEFINED_FUNCTION) => {
+                PgmqError::InstallationError(format!(
+                    "pgmq.set_vt does not accept a timestamp, this requires pgmq >= {MIN_SET_VT_TIMESTAMP_VERSION}: {e}"
+                ))
+            }
+            e => PgmqError::DatabaseError(e),
+        })?;
+        message_from_row(&updated)
+    }
+
+    /// Set the visibility time of an existing message to the absolute time `at`.
+    ///
+    /// A time in the past makes the message visible right away. Requires pgmq 1.10.0 or newer,
+    /// older versions fail with [`PgmqError::InstallationError`].
+    pub async fn set_vt_at<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+        at: chrono::DateTime<Utc>,
+    ) -> Result<Message<T>, PgmqError> {
+        self.set_vt_at_with_cxn(queue_name, msg_id, at, &self.connection)
             .await
     }
 
-    pub async fn send_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>, T: Serialize>(
+    pub async fn defer_all_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        executor: E,
+    ) -> Result<u64, PgmqError> {
+        check_input(queue_name)?;
+        let deferred = sqlx::query(&format!(
+            "UPDATE pgmq.{QUEUE_PREFIX}_{queue_name} SET vt = clock_timestamp() + make_interval(secs => $1::integer) WHERE vt > clock_timestamp();"
+        ))
+        .bind(vt)
+        .execute(executor)
+        .await?;
+        Ok(deferred.rows_affected())
+    }
+
+    /// Push back the visibility timeout of every message that is currently in-flight.
+    ///
+    /// Messages that are already visible are left untouched. Returns the number of
+    /// messages that were deferred.
+    pub async fn defer_all(&self, queue_name: &str, vt: i32) -> Result<u64, PgmqError> {
+        self.defer_all_with_cxn(queue_name, vt, &self.connection)
+            .await
+    }
+
+    pub async fn reset_read_ct_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+        executor: E,
+    ) -> Result<bool, PgmqError> {
+        check_input(queue_name)?;
+        let updated = sqlx::query(&format!(
+            "UPDATE pgmq.{QUEUE_PREFIX}_{queue_name} SET read_ct = 0 WHERE msg_id = $1::bigint;"
+        ))
+        .bind(msg_id)
+        .execute(executor)
+        .await?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    /// Reset the read count of a message to zero, e.g. before replaying it by hand.
+    /// Returns `false` if the message does not exist.
+    pub async fn reset_read_ct(&self, queue_name: &str, msg_id: i64) -> Result<bool, PgmqError> {
+        self.reset_read_ct_with_cxn(queue_name, msg_id, &self.connection)
+            .await
+    }
+
+    pub async fn compare_and_set_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: Serialize,
+    >(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+        expected: &T,
+        new: &T,
+        executor: E,
+    ) -> Result<bool, PgmqError> {
+        check_input(queue_name)?;
+        let expected = serde_json::to_value(expected)?;
+        let new = serde_json::to_value(new)?;
+        self.check_message_size(&new)?;
+        let updated = sqlx::query(&format!(
+            r#"
+            UPDATE pgmq.{QUEUE_PREFIX}_{queue_name}
+            SET message = $3::jsonb
+            WHERE msg_id = $1::bigint AND message = $2::jsonb;
+            "#
+        ))
+        .bind(msg_id)
+        .bind(expected)
+        .bind(new)
+        .execute(executor)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    /// Replace the body of a message with `new`, only if it still equals `expected`.
+    ///
+    /// Bodies are compared as jsonb, so key order and whitespace do not matter. Returns
+    /// `false` if the body changed since it was read, or the message no longer exists. The
+    /// message's visibility and read count are left unchanged.
+    pub async fn compare_and_set<T: Serialize>(
         &self,
         queue_name: &str,
+        msg_id: i64,
+        expected: &T,
+        new: &T,
+    ) -> Result<bool, PgmqError> {
+        self.compare_and_set_with_cxn(queue_name, msg_id, expected, new, &self.connection)
+            .await
+    }
+
+    pub async fn send_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>, T: Serialize>(
+        &self,
+        queue_name: &(impl AsQueueName + ?Sized),
         message: &T,
         executor: E,
     ) -> Result<i64, PgmqError> {
-        check_input(queue_name)?;
+        let queue_name = queue_name.as_queue_name()?;
         let msg = serde_json::json!(&message);
+        self.check_message_size(&msg)?;
         let prepared = sqlx::query(
             "SELECT send as msg_id from pgmq.send(queue_name=>$1::text, msg=>$2::jsonb, delay=>0::integer);",
         )
         .bind(queue_name)
         .bind(msg);
-        let sent = prepared.fetch_one(executor).await?;
-        Ok(sent.try_get("msg_id")?)
+        let sent = prepared
+            .fetch_one(executor)
+            .await
+            .map_err(|e| queue_not_found(queue_name, e))?;
+        let msg_id = sent.try_get("msg_id")?;
+        ClientCounters::add(&self.counters.sent, 1);
+        Ok(msg_id)
     }
 
     pub async fn send<T: Serialize>(
         &self,
-        queue_name: &str,
+        queue_name: &(impl AsQueueName + ?Sized),
         message: &T,
     ) -> Result<i64, PgmqError> {
-        self.send_with_cxn(queue_name, message, &self.connection)
-            .await
+        self.timed(
+            "send",
+            queue_name,
+            self.send_with_cxn(queue_name, message, &self.connection),
+        )
+        .await
     }
 
-    pub async fn send_delay_with_cxn<
-        'c,
-        E: sqlx::Executor<'c, Database = Postgres>,
-        T: Serialize,
-    >(
+    /// Send a message once the queue holds fewer than `max_depth` messages.
+    ///
+    /// While the queue is too deep, its length is polled until it drains or `timeout` elapses.
+    /// Returns `Ok(None)` without sending if the queue did not drain in time. The length
+    /// includes in-flight messages.
+    pub async fn send_with_limit<T: Serialize>(
         &self,
         queue_name: &str,
         message: &T,
-        delay: u32,
+        max_depth: i64,
+        timeout: std::time::Duration,
+    ) -> Result<Option<i64>, PgmqError> {
+        check_input(queue_name)?;
+        let start_time = std::time::Instant::now();
+        loop {
+            let queue_length = sqlx::query_scalar::<_, i64>(
+                "SELECT queue_length FROM pgmq.metrics(queue_name=>$1::text);",
+            )
+            .bind(queue_name)
+            .fetch_one(&self.connection)
+            .await?;
+            if queue_length < max_depth {
+                return self.send(queue_name, message).await.map(Some);
+            }
+            let elapsed = start_time.elapsed();
+            if elapsed >= timeout {
+                return Ok(None);
+            }
+            tokio::time::sleep(POLL_INTERVAL_DEFAULT.min(timeout - elapsed)).await;
+        }
+    }
+
+    pub async fn send_raw_json_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        json: &str,
         executor: E,
     ) -> Result<i64, PgmqError> {
         check_input(queue_name)?;
-        let msg = serde_json::json!(&message);
+        // validate without building a `serde_json::Value`
+        serde_json::from_str::<serde::de::IgnoredAny>(json)?;
+        self.check_message_len(json.len())?;
         let sent = sqlx::query(
-            "SELECT send as msg_id from pgmq.send(queue_name=>$1::text, msg=>$2::jsonb, delay=>$3::int);",
+            "SELECT send as msg_id from pgmq.send(queue_name=>$1::text, msg=>$2::jsonb, delay=>0::integer);",
         )
         .bind(queue_name)
-        .bind(msg)
-        .bind(delay as i32)
+        .bind(json)
         .fetch_one(executor)
-        .await?;
-        Ok(sent.try_get("msg_id")?)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        let msg_id = sent.try_get("msg_id")?;
+        ClientCounters::add(&self.counters.sent, 1);
+        Ok(msg_id)
     }
 
-    pub async fn send_delay<T: Serialize>(
+    /// Send a message that is already serialized as JSON.
+    ///
+    /// The string is checked to be well-formed JSON, returning a [`PgmqError::JsonParsingError`]
+    /// if it is not, and is then passed to Postgres as-is.
+    pub async fn send_raw_json(&self, queue_name: &str, json: &str) -> Result<i64, PgmqError> {
+        self.send_raw_json_with_cxn(queue_name, json, &self.connection)
+            .await
+    }
+
+    pub async fn send_batch_raw_json_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
         &self,
         queue_name: &str,
-        message: &T,
-        delay: u32,
-    ) -> Result<i64, PgmqError> {
-        self.send_delay_with_cxn(queue_name, message, delay, &self.connection)
+        msgs: &[&str],
+        executor: E,
+    ) -> Result<Vec<i64>, PgmqError> {
+        check_input(queue_name)?;
+        if msgs.is_empty() {
+            return Ok(vec![]);
+        }
+        for json in msgs {
+            serde_json::from_str::<serde::de::IgnoredAny>(json)?;
+            self.check_message_len(json.len())?;
+        }
+        let msg_ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT send_batch as msg_id from pgmq.send_batch(queue_name=>$1::text, msgs=>$2::text[]::jsonb[], delay=>0::integer);",
+        )
+        .bind(queue_name)
+        .bind(msgs)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        ClientCounters::add(&self.counters.sent, msg_ids.len() as u64);
+        Ok(msg_ids)
+    }
+
+    /// Send a batch of messages that are already serialized as JSON.
+    ///
+    /// Every string is checked to be well-formed JSON before anything is sent, so a
+    /// [`PgmqError::JsonParsingError`] means no message was enqueued. Returns the new message
+    /// ids in input order; an empty slice sends nothing.
+    pub async fn send_batch_raw_json(
+        &self,
+        queue_name: &str,
+        msgs: &[&str],
+    ) -> Result<Vec<i64>, PgmqError> {
+        self.send_batch_raw_json_with_cxn(queue_name, msgs, &self.connection)
             .await
     }
 
-    pub async fn read_with_cxn<
+    pub async fn send_batch_partial_with_cxn<
         'c,
         E: sqlx::Executor<'c, Database = Postgres>,
-        T: for<'de> Deserialize<'de>,
+        T: Serialize,
     >(
         &self,
         queue_name: &str,
-        vt: i32,
+        messages: &[T],
         executor: E,
-    ) -> Result<Option<Message<T>>, PgmqError> {
+    ) -> Result<BatchSendResult, PgmqError> {
         check_input(queue_name)?;
+        let mut result = BatchSendResult::default();
+        let mut indexes = Vec::with_capacity(messages.len());
+        let mut msgs = Vec::with_capacity(messages.len());
+        for (index, message) in messages.iter().enumerate() {
+            match serde_json::to_value(message) {
+                Ok(msg) => {
+                    self.check_message_size(&msg)?;
+                    indexes.push(index);
+                    msgs.push(msg);
+                }
+                Err(e) => result.failed.push((index, e)),
+            }
+        }
+        if msgs.is_empty() {
+            return Ok(result);
+        }
+        let msg_ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT send_batch as msg_id from pgmq.send_batch(queue_name=>$1::text, msgs=>$2::jsonb[], delay=>0::integer);",
+        )
+        .bind(queue_name)
+        .bind(msgs)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        ClientCounters::add(&self.counters.sent, msg_ids.len() as u64);
+        result.sent = indexes.into_iter().zip(msg_ids).collect();
+        Ok(result)
+    }
+
+    /// Send a batch of messages, skipping the ones that fail to serialize.
+    ///
+    /// Messages that serialize are sent together in one statement; the others are reported
+    /// with their index in `messages` instead of failing the whole batch. Database errors and
+    /// [`PgmqError::MessageTooLarge`] still fail the batch as a whole.
+    pub async fn send_batch_partial<T: Serialize>(
+        &self,
+        queue_name: &str,
+        messages: &[T],
+    ) -> Result<BatchSendResult, PgmqError> {
+        self.send_batch_partial_with_cxn(queue_name, messages, &self.connection)
+            .await
+    }
+
+    pub async fn send_routed_with_cxn<'c, E, T: Serialize>(
+        &self,
+        routed: &[(&str, &T)],
+        executor: E,
+    ) -> Result<Vec<(String, i64)>, PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        // group by queue, keeping the index of each message in `routed`
+        let mut batches: Vec<(&str, Vec<usize>, Vec<serde_json::Value>)> = vec![];
+        for (index, (queue_name, message)) in routed.iter().enumerate() {
+            check_input(queue_name)?;
+            let msg = serde_json::to_value(message)?;
+            self.check_message_size(&msg)?;
+            match batches.iter_mut().find(|(name, _, _)| name == queue_name) {
+                Some((_, indexes, msgs)) => {
+                    indexes.push(index);
+                    msgs.push(msg);
+                }
+                None => batches.push((queue_name, vec![index], vec![msg])),
+            }
+        }
+        if batches.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut tx = executor.begin().await?;
+        let mut msg_ids = vec![0; routed.len()];
+        for (queue_name, indexes, msgs) in batches {
+            let ids: Vec<i64> = sqlx::query_scalar(
+                "SELECT send_batch as msg_id from pgmq.send_batch(queue_name=>$1::text, msgs=>$2::jsonb[], delay=>0::integer);",
+            )
+            .bind(queue_name)
+            .bind(msgs)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| queue_not_found(queue_name, e))?;
+            for (index, msg_id) in indexes.into_iter().zip(ids) {
+                msg_ids[index] = msg_id;
+            }
+        }
+        tx.commit().await?;
+        ClientCounters::add(&self.counters.sent, routed.len() as u64);
+        Ok(routed
+            .iter()
+            .zip(msg_ids)
+            .map(|((queue_name, _), msg_id)| (queue_name.to_string(), msg_id))
+            .collect())
+    }
+
+    /// Send messages to several queues at once, with one `send_batch` per queue.
+    ///
+    /// All batches are sent in a single transaction, so either every message is sent or none
+    /// are. Returns the queue and id of each message, in the order of `routed`.
+    pub async fn send_routed<T: Serialize>(
+        &self,
+        routed: &[(&str, &T)],
+    ) -> Result<Vec<(String, i64)>, PgmqError> {
+        self.send_routed_with_cxn(routed, &self.connection).await
+    }
+
+    pub async fn send_with_id_with_cxn<'c, E, T: Serialize>(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+        message: &T,
+        executor: E,
+    ) -> Result<bool, PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        check_input(queue_name)?;
+        let msg = serde_json::json!(&message);
+        self.check_message_size(&msg)?;
+        let mut tx = executor.begin().await?;
+        let inserted = insert_with_id(&mut tx, queue_name, msg_id, &msg, None).await?;
+        tx.commit().await?;
+        if inserted {
+            ClientCounters::add(&self.counters.sent, 1);
+        }
+        Ok(inserted)
+    }
+
+    /// Send a message with an explicit `msg_id`, e.g. when replaying archived messages.
+    ///
+    /// Returns `false` without changing anything if a message with that id is already in the
+    /// queue, so a replay can be re-run safely. The queue's id sequence is advanced past
+    /// `msg_id` so that later sends do not reuse it.
+    pub async fn send_with_id<T: Serialize>(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+        message: &T,
+    ) -> Result<bool, PgmqError> {
+        self.send_with_id_with_cxn(queue_name, msg_id, message, &self.connection)
+            .await
+    }
+
+    pub async fn send_upsert_with_cxn<'c, E, T: Serialize>(
+        &self,
+        queue_name: &str,
+        dedup_key: &str,
+        message: &T,
+        executor: E,
+    ) -> Result<i64, PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        check_input(queue_name)?;
+        let msg = serde_json::json!(&message);
+        self.check_message_size(&msg)?;
+        let headers = serde_json::json!({ DEDUP_KEY_HEADER: dedup_key });
+        let mut tx = executor.begin().await?;
+
+        // serialize concurrent upserts of the same key
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1::text || '.' || $2::text));")
+            .bind(queue_name)
+            .bind(dedup_key)
+            .execute(&mut *tx)
+            .await?;
+        // messages that are in-flight are left for their consumer
+        sqlx::query(&format!(
+            r#"
+            DELETE FROM pgmq.{QUEUE_PREFIX}_{queue_name}
+            WHERE headers->>'{DEDUP_KEY_HEADER}' = $1::text
+              AND (read_ct = 0 OR vt <= clock_timestamp());
+            "#
+        ))
+        .bind(dedup_key)
+        .execute(&mut *tx)
+        .await?;
+        let msg_id = sqlx::query_scalar::<_, i64>(
+            "SELECT send as msg_id from pgmq.send(queue_name=>$1::text, msg=>$2::jsonb, headers=>$3::jsonb, delay=>0::integer);",
+        )
+        .bind(queue_name)
+        .bind(msg)
+        .bind(headers)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        ClientCounters::add(&self.counters.sent, 1);
+        Ok(msg_id)
+    }
+
+    /// Send a message, replacing any pending message with the same `dedup_key`.
+    ///
+    /// Pending messages are those that have not been read yet or whose visibility timeout has
+    /// expired. Messages currently being processed by a consumer are not replaced.
+    /// Returns the id of the new message.
+    pub async fn send_upsert<T: Serialize>(
+        &self,
+        queue_name: &str,
+        dedup_key: &str,
+        message: &T,
+    ) -> Result<i64, PgmqError> {
+        self.send_upsert_with_cxn(queue_name, dedup_key, message, &self.connection)
+            .await
+    }
+
+    pub async fn send_delay_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: Serialize,
+    >(
+        &self,
+        queue_name: &str,
+        message: &T,
+        delay: u32,
+        executor: E,
+    ) -> Result<i64, PgmqError> {
+        check_input(queue_name)?;
+        let msg = serde_json::json!(&message);
+        self.check_message_size(&msg)?;
+        let sent = sqlx::query(
+            "SELECT send as msg_id from pgmq.send(queue_name=>$1::text, msg=>$2::jsonb, delay=>$3::int);",
+        )
+        .bind(queue_name)
+        .bind(msg)
+        .bind(delay as i32)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        let msg_id = sent.try_get("msg_id")?;
+        ClientCounters::add(&self.counters.sent, 1);
+        Ok(msg_id)
+    }
+
+    pub async fn send_delay<T: Serialize>(
+        &self,
+        queue_name: &str,
+        message: &T,
+        delay: u32,
+    ) -> Result<i64, PgmqError> {
+        self.timed(
+            "send_delay",
+            queue_name,
+            self.send_delay_with_cxn(queue_name, message, delay, &self.connection),
+        )
+        .await
+    }
+
+    pub async fn send_with_expiry_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: Serialize,
+    >(
+        &self,
+        queue_name: &str,
+        message: &T,
+        expires_in: std::time::Duration,
+        executor: E,
+    ) -> Result<i64, PgmqError> {
+        check_input(queue_name)?;
+        let msg = serde_json::json!(&message);
+        self.check_message_size(&msg)?;
+        // the expiry is taken from the database clock, like the checks against it
+        let msg_id = sqlx::query_scalar::<_, i64>(&format!(
+            r#"
+            SELECT send as msg_id from pgmq.send(
+                queue_name=>$1::text,
+                msg=>$2::jsonb,
+                headers=>jsonb_build_object(
+                    '{EXPIRES_AT_HEADER}',
+                    clock_timestamp() + make_interval(secs => $3::float8)
+                ),
+                delay=>0::integer
+            );
+            "#
+        ))
+        .bind(queue_name)
+        .bind(msg)
+        .bind(expires_in.as_secs_f64())
+        .fetch_one(executor)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        ClientCounters::add(&self.counters.sent, 1);
+        Ok(msg_id)
+    }
+
+    /// Send a message that is dropped if it is not read within `expires_in`.
+    ///
+    /// The expiry is stored in the [`EXPIRES_AT_HEADER`] header. Regular reads deliver expired
+    /// messages like any other: read with [`PGMQueueExt::read_unexpired`] to skip them, or
+    /// delete them with [`PGMQueueExt::expire_stale`].
+    pub async fn send_with_expiry<T: Serialize>(
+        &self,
+        queue_name: &str,
+        message: &T,
+        expires_in: std::time::Duration,
+    ) -> Result<i64, PgmqError> {
+        self.send_with_expiry_with_cxn(queue_name, message, expires_in, &self.connection)
+            .await
+    }
+
+    pub async fn expire_stale_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<u64, PgmqError> {
+        check_input(queue_name)?;
+        let deleted = sqlx::query(&format!(
+            "DELETE FROM pgmq.{QUEUE_PREFIX}_{queue_name} WHERE {};",
+            expired()
+        ))
+        .execute(executor)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?
+        .rows_affected();
+        ClientCounters::add(&self.counters.deleted, deleted);
+        Ok(deleted)
+    }
+
+    /// Delete the messages of a queue that are past their expiry, see
+    /// [`PGMQueueExt::send_with_expiry`]. Returns the number of messages deleted.
+    ///
+    /// In-flight messages are deleted too once they expire. A malformed expiry header is left
+    /// alone, the message never expires.
+    pub async fn expire_stale(&self, queue_name: &str) -> Result<u64, PgmqError> {
+        self.expire_stale_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    pub async fn read_unexpired_with_cxn<'c, E, T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+        executor: E,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        check_input(queue_name)?;
+        check_qty(qty)?;
+        let mut conn = executor.acquire().await?;
+        // queue tables of pgmq versions without message headers cannot hold an expiry
+        let has_headers: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM pg_attribute WHERE attrelid = to_regclass(format('pgmq.%I', $1::text)) AND attname = 'headers' AND NOT attisdropped);",
+        )
+        .bind(format!("{QUEUE_PREFIX}_{queue_name}").to_lowercase())
+        .fetch_one(&mut *conn)
+        .await?;
+        let rows = if has_headers {
+            sqlx::query(
+                &LeaseSql {
+                    limit: "$2::integer",
+                    unexpired: true,
+                    ..LeaseSql::new(queue_name)
+                }
+                .sql(),
+            )
+            .bind(vt)
+            .bind(qty)
+            .fetch_all(&mut *conn)
+            .await
+        } else {
+            sqlx::query(
+                r#"SELECT msg_id, read_ct, enqueued_at, vt, message from pgmq.read(queue_name=>$1::text, vt=>$2::integer, qty=>$3::integer)"#,
+            )
+            .bind(queue_name)
+            .bind(vt)
+            .bind(qty)
+            .fetch_all(&mut *conn)
+            .await
+        }
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        let mut messages = rows
+            .iter()
+            .map(message_from_row)
+            .collect::<Result<Vec<Message<T>>, _>>()?;
+        // UPDATE .. RETURNING does not preserve the order of the CTE
+        messages.sort_by_key(|m| m.msg_id);
+        ClientCounters::add(&self.counters.read, messages.len() as u64);
+        Ok(Some(messages))
+    }
+
+    /// Read up to `qty` messages in `msg_id` order, skipping the ones past their expiry (see
+    /// [`PGMQueueExt::send_with_expiry`]).
+    ///
+    /// Expired messages are left in the queue untouched, for [`PGMQueueExt::expire_stale`] to
+    /// delete. A message whose expiry header is not a well-formed timestamp never expires. The
+    /// filter needs the `headers` column of newer pgmq versions, on queues without it this
+    /// reads like `pgmq.read`. Unlike [`PGMQueueExt::read`] the read is not done by the extension's
+    /// `pgmq.read`, so the role needs `SELECT` and `UPDATE` on the queue table.
+    pub async fn read_unexpired<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
+        self.read_unexpired_with_cxn(queue_name, vt, qty, &self.connection)
+            .await
+    }
+
+    /// Like [`PGMQueueExt::read`], on the given executor.
+    pub async fn read_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &(impl AsQueueName + ?Sized),
+        vt: i32,
+        executor: E,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        let queue_name = queue_name.as_queue_name()?;
         let row = sqlx::query(
             r#"SELECT msg_id, read_ct, enqueued_at, vt, message from pgmq.read(queue_name=>$1::text, vt=>$2::integer, qty=>$3::integer)"#,
         )
@@ -356,15 +2173,9 @@ impl PGMQueueExt {
         match row {
             Some(row) => {
                 // happy path - successfully read a message
-                let raw_msg = row.try_get("message")?;
-                let parsed_msg = serde_json::from_value::<T>(raw_msg)?;
-                Ok(Some(Message {
-                    msg_id: row.try_get("msg_id")?,
-                    vt: row.try_get("vt")?,
-                    read_ct: row.try_get("read_ct")?,
-                    enqueued_at: row.try_get("enqueued_at")?,
-                    message: parsed_msg,
-                }))
+                let message = message_from_row(&row)?;
+                ClientCounters::add(&self.counters.read, 1);
+                Ok(Some(message))
             }
             None => {
                 // no message found
@@ -372,12 +2183,1327 @@ impl PGMQueueExt {
             }
         }
     }
-    pub async fn read<T: for<'de> Deserialize<'de>>(
+
+    /// Read one message, making it invisible for `vt` seconds.
+    ///
+    /// Messages past their expiry (see [`PGMQueueExt::send_with_expiry`]) are read like any
+    /// other, use [`PGMQueueExt::read_unexpired`] to skip them.
+    pub async fn read<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &(impl AsQueueName + ?Sized),
+        vt: i32,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        let queue_name = queue_name.as_queue_name()?;
+        self.timed(
+            "read",
+            queue_name,
+            self.read_with_cxn(queue_name, vt, &self.connection),
+        )
+        .await
+    }
+
+    pub async fn read_no_count_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        executor: E,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        let row = sqlx::query(
+            &LeaseSql {
+                count_read: false,
+                ..LeaseSql::new(queue_name)
+            }
+            .sql(),
+        )
+        .bind(vt)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        let message = row.as_ref().map(message_from_row).transpose()?;
+        if message.is_some() {
+            ClientCounters::add(&self.counters.read, 1);
+        }
+        Ok(message)
+    }
+
+    /// Read one message, making it invisible for `vt` seconds, without incrementing its
+    /// `read_ct`.
+    ///
+    /// Use it for monitoring reads that should not count against a message's retry budget.
+    /// Unlike [`PGMQueueExt::read`], `read_ct` and `last_read_at` are left as they were, so the
+    /// message does not move closer to a dead-letter queue. Unlike
+    /// [`PGMQueueExt::get_message`], the message is leased: other consumers will not see it
+    /// until `vt` passes.
+    pub async fn read_no_count<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        self.read_no_count_with_cxn(queue_name, vt, &self.connection)
+            .await
+    }
+
+    pub async fn read_array_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+        const N: usize,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        executor: E,
+    ) -> Result<([Option<Message<T>>; N], usize), PgmqError> {
+        check_input(queue_name)?;
+        let qty = i32::try_from(N).map_err(|_| PgmqError::InvalidQuantity { qty: i32::MAX })?;
+        check_qty(qty)?;
+        let mut messages = std::array::from_fn(|_| None);
+        let mut read = 0;
+        // rows are decoded as they arrive instead of being collected first
+        let mut rows = sqlx::query(
+            r#"SELECT msg_id, read_ct, enqueued_at, vt, message from pgmq.read(queue_name=>$1::text, vt=>$2::integer, qty=>$3::integer)"#,
+        )
+        .bind(queue_name)
+        .bind(vt)
+        .bind(qty)
+        .fetch(executor);
+        while let Some(row) = rows.try_next().await? {
+            messages[read] = Some(message_from_row(&row)?);
+            read += 1;
+        }
+        ClientCounters::add(&self.counters.read, read as u64);
+        Ok((messages, read))
+    }
+
+    /// Read up to `N` messages into a fixed-size array, returning it with the number of
+    /// messages read.
+    ///
+    /// The first `read` slots hold the messages in `msg_id` order and the rest are `None`.
+    /// Unlike the batch reads no `Vec` is allocated for the batch, which helps consumers
+    /// reading small, fixed batches in a tight loop. `N` must be at least 1.
+    pub async fn read_array<T: for<'de> Deserialize<'de>, const N: usize>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+    ) -> Result<([Option<Message<T>>; N], usize), PgmqError> {
+        self.read_array_with_cxn(queue_name, vt, &self.connection)
+            .await
+    }
+
+    pub async fn get_message_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+        executor: E,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        let row = sqlx::query(&format!(
+            "SELECT msg_id, read_ct, enqueued_at, vt, message FROM pgmq.{QUEUE_PREFIX}_{queue_name} WHERE msg_id = $1::bigint;"
+        ))
+        .bind(msg_id)
+        .fetch_optional(executor)
+        .await?;
+        row.as_ref().map(message_from_row).transpose()
+    }
+
+    /// Look up a single message by id without reading it.
+    ///
+    /// Unlike a read, the message's visibility timeout and read count are left untouched, and
+    /// the message is returned even if it is currently invisible. Returns `None` once the
+    /// message was deleted or archived.
+    pub async fn get_message<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        self.get_message_with_cxn(queue_name, msg_id, &self.connection)
+            .await
+    }
+
+    pub async fn count_enqueued_since_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        since: chrono::DateTime<Utc>,
+        executor: E,
+    ) -> Result<i64, PgmqError> {
+        check_input(queue_name)?;
+        let count = sqlx::query_scalar(&format!(
+            "SELECT count(*) FROM pgmq.{QUEUE_PREFIX}_{queue_name} WHERE enqueued_at >= $1::timestamptz;"
+        ))
+        .bind(since)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        Ok(count)
+    }
+
+    /// Count the messages in a queue that were enqueued at or after `since`, visible or not.
+    ///
+    /// Messages already deleted or archived are not counted. Queue tables have no index on
+    /// `enqueued_at`, so this scans the whole queue; on large queues create one with
+    /// `CREATE INDEX ON pgmq.q_<queue_name> (enqueued_at)`.
+    pub async fn count_enqueued_since(
+        &self,
+        queue_name: &str,
+        since: chrono::DateTime<Utc>,
+    ) -> Result<i64, PgmqError> {
+        self.count_enqueued_since_with_cxn(queue_name, since, &self.connection)
+            .await
+    }
+
+    pub async fn read_enqueued_since_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        since: chrono::DateTime<Utc>,
+        limit: i64,
+        executor: E,
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT msg_id, read_ct, enqueued_at, vt, message
+            FROM pgmq.{QUEUE_PREFIX}_{queue_name}
+            WHERE enqueued_at >= $1::timestamptz
+            ORDER BY enqueued_at ASC, msg_id ASC
+            LIMIT $2::bigint
+            "#
+        ))
+        .bind(since)
+        .bind(limit)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        rows.iter().map(message_from_row).collect()
+    }
+
+    /// Up to `limit` messages enqueued at or after `since`, oldest first, without reading them.
+    ///
+    /// Like [`get_message`](Self::get_message), visibility timeouts and read counts are left
+    /// untouched and invisible messages are included. See
+    /// [`count_enqueued_since`](Self::count_enqueued_since) for indexing `enqueued_at`.
+    pub async fn read_enqueued_since<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        since: chrono::DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        self.read_enqueued_since_with_cxn(queue_name, since, limit, &self.connection)
+            .await
+    }
+
+    pub async fn message_vt_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+        executor: E,
+    ) -> Result<Option<chrono::DateTime<Utc>>, PgmqError> {
+        check_input(queue_name)?;
+        let vt = sqlx::query_scalar(&format!(
+            "SELECT vt FROM pgmq.{QUEUE_PREFIX}_{queue_name} WHERE msg_id = $1::bigint;"
+        ))
+        .bind(msg_id)
+        .fetch_optional(executor)
+        .await?;
+        Ok(vt)
+    }
+
+    /// The time at which a message becomes visible again, without reading it.
+    ///
+    /// Like [`PGMQueueExt::get_message`] the message is left untouched and its body is not
+    /// fetched. A time in the past means the message is visible now. Returns `None` once the
+    /// message was deleted or archived.
+    pub async fn message_vt(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+    ) -> Result<Option<chrono::DateTime<Utc>>, PgmqError> {
+        self.message_vt_with_cxn(queue_name, msg_id, &self.connection)
+            .await
+    }
+
+    pub async fn get_messages_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        msg_ids: &[i64],
+        executor: E,
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        if msg_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT msg_id, read_ct, enqueued_at, vt, message
+            FROM pgmq.{QUEUE_PREFIX}_{queue_name}
+            WHERE msg_id = ANY($1::bigint[])
+            ORDER BY array_position($1::bigint[], msg_id)
+            "#
+        ))
+        .bind(msg_ids)
+        .fetch_all(executor)
+        .await?;
+        rows.iter().map(message_from_row).collect()
+    }
+
+    /// Look up several messages by id in one query, see [`PGMQueueExt::get_message`].
+    ///
+    /// Ids of messages that no longer exist are skipped. The remaining messages are returned
+    /// in the order their ids appear in `msg_ids`.
+    pub async fn get_messages<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        msg_ids: &[i64],
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        self.get_messages_with_cxn(queue_name, msg_ids, &self.connection)
+            .await
+    }
+
+    pub async fn read_after_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        after_msg_id: i64,
+        limit: i64,
+        executor: E,
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT msg_id, read_ct, enqueued_at, vt, message
+            FROM pgmq.{QUEUE_PREFIX}_{queue_name}
+            WHERE msg_id > $1::bigint
+            ORDER BY msg_id
+            LIMIT $2::bigint
+            "#
+        ))
+        .bind(after_msg_id)
+        .bind(limit)
+        .fetch_all(executor)
+        .await?;
+        rows.iter().map(message_from_row).collect()
+    }
+
+    /// Fetch up to `limit` messages with an id greater than `after_msg_id`, in id order.
+    ///
+    /// Like [`PGMQueueExt::get_message`] this does not read the messages: their visibility
+    /// timeout and read count are left untouched and invisible messages are included. Callers
+    /// tailing a queue track the last returned `msg_id` themselves and pass it back in.
+    pub async fn read_after<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        after_msg_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        self.read_after_with_cxn(queue_name, after_msg_id, limit, &self.connection)
+            .await
+    }
+
+    pub async fn read_typed_and_raw_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        executor: E,
+    ) -> Result<Option<(Message<T>, serde_json::Value)>, PgmqError> {
+        let Some(raw) = self
+            .read_with_cxn::<_, serde_json::Value>(queue_name, vt, executor)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let parsed = Message {
+            msg_id: raw.msg_id,
+            vt: raw.vt,
+            read_ct: raw.read_ct,
+            enqueued_at: raw.enqueued_at,
+            message: T::deserialize(&raw.message)?,
+        };
+        Ok(Some((parsed, raw.message)))
+    }
+
+    /// Read a single message, returning both the message parsed into `T` and its original JSON body.
+    ///
+    /// Useful when forwarding the message downstream, since re-serializing `T` may drop
+    /// unknown fields or reorder keys.
+    pub async fn read_typed_and_raw<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+    ) -> Result<Option<(Message<T>, serde_json::Value)>, PgmqError> {
+        self.read_typed_and_raw_with_cxn(queue_name, vt, &self.connection)
+            .await
+    }
+
+    pub async fn read_raw_value_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        executor: E,
+    ) -> Result<Option<Message<Box<RawValue>>>, PgmqError> {
+        check_input(queue_name)?;
+        // a `serde_json::Value` cannot be deserialized into a `RawValue`, so read the body as text
+        let row = sqlx::query(
+            r#"SELECT msg_id, read_ct, enqueued_at, vt, message::text AS message from pgmq.read(queue_name=>$1::text, vt=>$2::integer, qty=>$3::integer)"#,
+        )
+        .bind(queue_name)
+        .bind(vt)
+        .bind(1)
+        .fetch_optional(executor)
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let message = RawValue::from_string(row.try_get("message")?)?;
+        ClientCounters::add(&self.counters.read, 1);
+        Ok(Some(Message {
+            msg_id: row.try_get("msg_id")?,
+            vt: row.try_get("vt")?,
+            read_ct: row.try_get("read_ct")?,
+            enqueued_at: row.try_get("enqueued_at")?,
+            message,
+        }))
+    }
+
+    /// Read a single message with its body as unparsed JSON, for forwarding it as-is.
+    ///
+    /// The body is the text rendering of the stored `jsonb`, so keys may be reordered and
+    /// whitespace normalized compared to what was sent, but it is never parsed into a
+    /// `serde_json::Value` and re-serialized.
+    pub async fn read_raw_value(
+        &self,
+        queue_name: &str,
+        vt: i32,
+    ) -> Result<Option<Message<Box<RawValue>>>, PgmqError> {
+        self.read_raw_value_with_cxn(queue_name, vt, &self.connection)
+            .await
+    }
+
+    pub async fn read_tagged_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        executor: E,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        let Some(raw) = self
+            .read_with_cxn::<_, serde_json::Value>(queue_name, vt, executor)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let message = match T::deserialize(&raw.message) {
+            Ok(message) => message,
+            Err(e) => {
+                let tag = raw
+                    .message
+                    .get(MESSAGE_TAG_FIELD)
+                    .and_then(serde_json::Value::as_str);
+                return Err(match tag {
+                    Some(tag) if !is_known_tag::<T>(tag) => PgmqError::UnknownMessageTag {
+                        tag: tag.to_owned(),
+                        msg_id: raw.msg_id,
+                    },
+                    _ => e.into(),
+                });
+            }
+        };
+        Ok(Some(Message {
+            msg_id: raw.msg_id,
+            vt: raw.vt,
+            read_ct: raw.read_ct,
+            enqueued_at: raw.enqueued_at,
+            message,
+        }))
+    }
+
+    /// Read a single message into an enum tagged with `#[serde(tag = "type")]`.
+    ///
+    /// Behaves like [`read`](Self::read), except that a message whose `type` does not match any
+    /// variant of `T` is reported as [`PgmqError::UnknownMessageTag`] with the offending tag and
+    /// message id, so it can be routed elsewhere (e.g. archived or sent to a dead letter queue).
+    pub async fn read_tagged<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        self.read_tagged_with_cxn(queue_name, vt, &self.connection)
+            .await
+    }
+
+    pub async fn read_projection_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        json_keys: &[&str],
+        executor: E,
+    ) -> Result<Option<(MessageMeta, serde_json::Value)>, PgmqError> {
+        check_input(queue_name)?;
+        for key in json_keys {
+            check_json_key(key)?;
+        }
+        let row = sqlx::query(&LeaseSql {
+            returning: "m.msg_id, m.read_ct, m.enqueued_at, m.vt, COALESCE(
+                (SELECT jsonb_object_agg(k, m.message->k) FROM unnest($2::text[]) AS k WHERE m.message ? k),
+                '{}'::jsonb
+            ) AS message",
+            ..LeaseSql::new(queue_name)
+        }
+        .sql())
+        .bind(vt)
+        .bind(json_keys)
+        .fetch_optional(executor)
+        .await?;
+        match row {
+            Some(row) => {
+                ClientCounters::add(&self.counters.read, 1);
+                let meta = MessageMeta {
+                    msg_id: row.try_get("msg_id")?,
+                    vt: row.try_get("vt")?,
+                    read_ct: row.try_get("read_ct")?,
+                    enqueued_at: row.try_get("enqueued_at")?,
+                };
+                Ok(Some((meta, row.try_get("message")?)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Read a single message, returning only the top-level `json_keys` of its body.
+    ///
+    /// The projection happens in Postgres, so the rest of the body is never sent over the wire.
+    /// Keys missing from the body are left out of the returned object. Keys must be
+    /// alphanumeric or underscores.
+    pub async fn read_projection(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        json_keys: &[&str],
+    ) -> Result<Option<(MessageMeta, serde_json::Value)>, PgmqError> {
+        self.read_projection_with_cxn(queue_name, vt, json_keys, &self.connection)
+            .await
+    }
+
+    pub async fn read_bytes_raw_with_cxn<'c, E>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        executor: E,
+    ) -> Result<Option<(MessageMeta, Vec<u8>)>, PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        check_input(queue_name)?;
+        let queue_table = format!("pgmq.{QUEUE_PREFIX}_{queue_name}");
+        let mut conn = executor.acquire().await?;
+        // check before reading, a mismatch would otherwise only surface after the read
+        let column_type: Option<String> = sqlx::query_scalar(
+            "SELECT format_type(atttypid, atttypmod) FROM pg_attribute WHERE attrelid = to_regclass($1::text) AND attname = 'message' AND NOT attisdropped;",
+        )
+        .bind(&queue_table)
+        .fetch_optional(&mut *conn)
+        .await?;
+        let Some(column_type) = column_type else {
+            return Err(PgmqError::QueueNotFound {
+                name: queue_name.to_owned(),
+            });
+        };
+        if column_type != "bytea" {
+            return Err(PgmqError::UnexpectedColumnType {
+                name: queue_name.to_owned(),
+                expected: "bytea".to_owned(),
+                found: column_type,
+            });
+        }
+        let row = sqlx::query(&LeaseSql::new(queue_name).sql())
+            .bind(vt)
+            .fetch_optional(&mut *conn)
+            .await?;
+        match row {
+            Some(row) => {
+                ClientCounters::add(&self.counters.read, 1);
+                let meta = MessageMeta {
+                    msg_id: row.try_get("msg_id")?,
+                    vt: row.try_get("vt")?,
+                    read_ct: row.try_get("read_ct")?,
+                    enqueued_at: row.try_get("enqueued_at")?,
+                };
+                Ok(Some((meta, row.try_get("message")?)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Read a single message from a queue whose `message` column is `bytea`, returning the raw
+    /// bytes.
+    ///
+    /// Queues created by pgmq store `jsonb`, this is meant for tables altered to hold binary
+    /// payloads such as MessagePack. Errors with [`PgmqError::UnexpectedColumnType`] before
+    /// reading anything if the column is not `bytea`.
+    pub async fn read_bytes_raw(
+        &self,
+        queue_name: &str,
+        vt: i32,
+    ) -> Result<Option<(MessageMeta, Vec<u8>)>, PgmqError> {
+        self.read_bytes_raw_with_cxn(queue_name, vt, &self.connection)
+            .await
+    }
+
+    pub async fn read_batch_ordered_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+        order: ReadOrder,
+        executor: E,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
+        self.read_batch_locked(queue_name, vt, qty, order, ReadLock::SkipLocked, executor)
+            .await
+    }
+
+    /// Read up to `qty` messages, delivered in the given [`ReadOrder`].
+    ///
+    /// [`ReadOrder::MsgId`] matches the ordering of `pgmq.read`. [`ReadOrder::EnqueuedAt`]
+    /// tracks enqueue time instead, which matters for partitioned queues; it needs an
+    /// index on `enqueued_at` to avoid sorting every visible message on each read.
+    pub async fn read_batch_ordered<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+        order: ReadOrder,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
+        self.read_batch_ordered_with_cxn(queue_name, vt, qty, order, &self.connection)
+            .await
+    }
+
+    pub async fn read_nowait_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        executor: E,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        let row = sqlx::query(
+            &LeaseSql {
+                lock: "FOR UPDATE NOWAIT",
+                ..LeaseSql::new(queue_name)
+            }
+            .sql(),
+        )
+        .bind(vt)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db) if db.code().as_deref() == Some(LOCK_NOT_AVAILABLE) => {
+                PgmqError::WouldBlock {
+                    name: queue_name.to_owned(),
+                }
+            }
+            _ => PgmqError::DatabaseError(e),
+        })?;
+        let message = row.as_ref().map(message_from_row).transpose()?;
+        if message.is_some() {
+            ClientCounters::add(&self.counters.read, 1);
+        }
+        Ok(message)
+    }
+
+    /// Read the message at the head of the queue, failing instead of skipping it when it is
+    /// locked.
+    ///
+    /// [`read`](Self::read) skips rows locked by other transactions and moves on to the next
+    /// visible message. This reads with `FOR UPDATE NOWAIT` and returns
+    /// [`PgmqError::WouldBlock`] right away when the head message is locked, so callers with
+    /// strict ordering requirements can back off rather than read out of order.
+    pub async fn read_nowait<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        self.read_nowait_with_cxn(queue_name, vt, &self.connection)
+            .await
+    }
+
+    pub async fn read_strict_fifo_with_cxn<'c, E, T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        executor: E,
+    ) -> Result<Option<Message<T>>, PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        check_input(queue_name)?;
+        let mut tx = executor.begin().await?;
+        // one strict reader per queue at a time
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('pgmq.strict_fifo.' || $1::text));")
+            .bind(queue_name)
+            .execute(&mut *tx)
+            .await?;
+        // only the oldest message may be read, and only once it is visible
+        let row = sqlx::query(
+            &LeaseSql {
+                head_only: true,
+                lock: "FOR UPDATE",
+                ..LeaseSql::new(queue_name)
+            }
+            .sql(),
+        )
+        .bind(vt)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        let message = row.as_ref().map(message_from_row).transpose()?;
+        tx.commit().await?;
+        if message.is_some() {
+            ClientCounters::add(&self.counters.read, 1);
+        }
+        Ok(message)
+    }
+
+    /// Read the oldest message of the queue, but only while no other message is checked out.
+    ///
+    /// Returns `Ok(None)` while the oldest message is in flight or delayed, so the next message
+    /// is never delivered before the previous one was deleted or archived, and at most one
+    /// message is in flight at a time. Reads are serialized with a per-queue advisory lock.
+    /// This gives strict ordering at the cost of throughput: a queue read this way is
+    /// processed one message at a time, however many consumers there are. A message whose
+    /// `vt` expires is delivered again before any later message. Mixing this with
+    /// [`read`](Self::read) on the same queue voids the guarantee.
+    pub async fn read_strict_fifo<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        self.read_strict_fifo_with_cxn(queue_name, vt, &self.connection)
+            .await
+    }
+
+    pub async fn read_batch_with_lock_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+        lock: ReadLock,
+        executor: E,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
+        self.read_batch_locked(queue_name, vt, qty, ReadOrder::MsgId, lock, executor)
+            .await
+    }
+
+    /// Read up to `qty` messages in `msg_id` order, selecting them with the given [`ReadLock`].
+    ///
+    /// [`ReadLock::SkipLocked`] behaves like a regular batch read. [`ReadLock::NoLock`] skips
+    /// the row locks and is **only safe when a single consumer reads from the queue**: with
+    /// concurrent consumers the same message can be delivered to more than one of them.
+    pub async fn read_batch_with_lock<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+        lock: ReadLock,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
+        self.read_batch_with_lock_with_cxn(queue_name, vt, qty, lock, &self.connection)
+            .await
+    }
+
+    async fn read_batch_locked<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+        order: ReadOrder,
+        lock: ReadLock,
+        executor: E,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
+        check_input(queue_name)?;
+        check_qty(qty)?;
+        let rows = sqlx::query(
+            &LeaseSql {
+                order_by: order.order_by(),
+                limit: "$2::integer",
+                lock: lock.clause(),
+                ..LeaseSql::new(queue_name)
+            }
+            .sql(),
+        )
+        .bind(vt)
+        .bind(qty)
+        .fetch_all(executor)
+        .await?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        // UPDATE .. RETURNING does not preserve the order of the CTE
+        let mut messages = rows
+            .iter()
+            .map(message_from_row)
+            .collect::<Result<Vec<Message<T>>, _>>()?;
+        match order {
+            ReadOrder::MsgId => messages.sort_by_key(|m| m.msg_id),
+            ReadOrder::EnqueuedAt => messages.sort_by_key(|m| (m.enqueued_at, m.msg_id)),
+        }
+        ClientCounters::add(&self.counters.read, messages.len() as u64);
+        Ok(Some(messages))
+    }
+
+    pub async fn read_by_header_priority_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+        executor: E,
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        check_qty(qty)?;
+        // a missing or non-integer priority sorts as null rather than failing the cast
+        let priority = format!(
+            "CASE WHEN headers->>'{PRIORITY_HEADER}' ~ '^-?[0-9]{{1,9}}$' \
+             THEN (headers->>'{PRIORITY_HEADER}')::integer END"
+        );
+        let rows = sqlx::query(
+            &LeaseSql {
+                columns: &format!(", {priority} AS priority"),
+                order_by: "priority DESC NULLS LAST, msg_id",
+                limit: "$2::integer",
+                returning: "m.msg_id, m.read_ct, m.enqueued_at, m.vt, m.message, cte.priority",
+                ..LeaseSql::new(queue_name)
+            }
+            .sql(),
+        )
+        .bind(vt)
+        .bind(qty)
+        .fetch_all(executor)
+        .await?;
+        // UPDATE .. RETURNING does not preserve the order of the CTE
+        let mut prioritized = rows
+            .iter()
+            .map(|row| {
+                Ok((
+                    row.try_get::<Option<i32>, _>("priority")?,
+                    message_from_row(row)?,
+                ))
+            })
+            .collect::<Result<Vec<(Option<i32>, Message<T>)>, PgmqError>>()?;
+        prioritized.sort_by(|(a, ma), (b, mb)| match (a, b) {
+            (Some(a), Some(b)) => b.cmp(a).then(ma.msg_id.cmp(&mb.msg_id)),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => ma.msg_id.cmp(&mb.msg_id),
+        });
+        let messages: Vec<Message<T>> = prioritized.into_iter().map(|(_, m)| m).collect();
+        ClientCounters::add(&self.counters.read, messages.len() as u64);
+        Ok(messages)
+    }
+
+    /// Read up to `qty` messages, highest [`PRIORITY_HEADER`] first.
+    ///
+    /// Messages are ordered by the integer `priority` header descending, then by `msg_id`.
+    /// Messages without headers, or whose priority is not an integer, are delivered last.
+    pub async fn read_by_header_priority<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        self.read_by_header_priority_with_cxn(queue_name, vt, qty, &self.connection)
+            .await
+    }
+
+    pub async fn enable_priority_with_cxn<'c, E>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<(), PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        check_input(queue_name)?;
+        let mut tx = executor.begin().await?;
+        sqlx::query(&format!(
+            "ALTER TABLE pgmq.{QUEUE_PREFIX}_{queue_name} ADD COLUMN IF NOT EXISTS priority SMALLINT NOT NULL DEFAULT 0;"
+        ))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| queue_not_found(queue_name, e))?;
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {QUEUE_PREFIX}_{queue_name}_priority_idx ON pgmq.{QUEUE_PREFIX}_{queue_name} (priority DESC, msg_id);"
+        ))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Add the `priority` column used by [`PGMQueueExt::send_prioritized`] and
+    /// [`PGMQueueExt::read_prioritized`] to an existing queue.
+    ///
+    /// This is an optional install step, run once per queue. It is equivalent to:
+    ///
+    /// ```sql
+    /// ALTER TABLE pgmq.q_<queue_name> ADD COLUMN IF NOT EXISTS priority SMALLINT NOT NULL DEFAULT 0;
+    /// CREATE INDEX IF NOT EXISTS q_<queue_name>_priority_idx ON pgmq.q_<queue_name> (priority DESC, msg_id);
+    /// ```
+    ///
+    /// Messages sent with the regular `send` methods get priority 0. The archive table is not
+    /// changed, so archived messages do not keep their priority.
+    pub async fn enable_priority(&self, queue_name: &str) -> Result<(), PgmqError> {
+        self.enable_priority_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    pub async fn send_prioritized_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: Serialize,
+    >(
+        &self,
+        queue_name: &str,
+        message: &T,
+        priority: i16,
+        executor: E,
+    ) -> Result<i64, PgmqError> {
+        check_input(queue_name)?;
+        let msg = serde_json::json!(&message);
+        self.check_message_size(&msg)?;
+        let msg_id = sqlx::query_scalar(&format!(
+            "INSERT INTO pgmq.{QUEUE_PREFIX}_{queue_name} (vt, message, priority) VALUES (clock_timestamp(), $1::jsonb, $2::smallint) RETURNING msg_id;"
+        ))
+        .bind(msg)
+        .bind(priority)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| priority_unavailable(queue_name, e))?;
+        ClientCounters::add(&self.counters.sent, 1);
+        Ok(msg_id)
+    }
+
+    /// Send a message with a priority, see [`PGMQueueExt::read_prioritized`].
+    ///
+    /// Higher values are delivered first. The queue needs the `priority` column added by
+    /// [`PGMQueueExt::enable_priority`], otherwise this returns
+    /// [`PgmqError::InstallationError`].
+    pub async fn send_prioritized<T: Serialize>(
+        &self,
+        queue_name: &str,
+        message: &T,
+        priority: i16,
+    ) -> Result<i64, PgmqError> {
+        self.send_prioritized_with_cxn(queue_name, message, priority, &self.connection)
+            .await
+    }
+
+    pub async fn read_prioritized_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+        executor: E,
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        check_qty(qty)?;
+        let rows = sqlx::query(
+            &LeaseSql {
+                order_by: "priority DESC, msg_id",
+                limit: "$2::integer",
+                returning: "m.msg_id, m.read_ct, m.enqueued_at, m.vt, m.message, m.priority",
+                ..LeaseSql::new(queue_name)
+            }
+            .sql(),
+        )
+        .bind(vt)
+        .bind(qty)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| priority_unavailable(queue_name, e))?;
+        // UPDATE .. RETURNING does not preserve the order of the CTE
+        let mut prioritized = rows
+            .iter()
+            .map(|row| Ok((row.try_get::<i16, _>("priority")?, message_from_row(row)?)))
+            .collect::<Result<Vec<(i16, Message<T>)>, PgmqError>>()?;
+        prioritized.sort_by(|(a, ma), (b, mb)| b.cmp(a).then(ma.msg_id.cmp(&mb.msg_id)));
+        let messages: Vec<Message<T>> = prioritized.into_iter().map(|(_, m)| m).collect();
+        ClientCounters::add(&self.counters.read, messages.len() as u64);
+        Ok(messages)
+    }
+
+    /// Read up to `qty` visible messages, highest priority first.
+    ///
+    /// Messages are ordered by `priority DESC, msg_id`, so messages of the same priority are
+    /// delivered in the order they were sent. Unlike [`PGMQueueExt::read_by_header_priority`],
+    /// the priority is a dedicated, indexed column; see [`PGMQueueExt::enable_priority`] for
+    /// the required schema addition.
+    pub async fn read_prioritized<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        qty: i32,
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        self.read_prioritized_with_cxn(queue_name, vt, qty, &self.connection)
+            .await
+    }
+
+    pub async fn read_claim_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        consumer_id: &str,
+        vt: i32,
+        executor: E,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        // same selection as pgmq.read, but the claim is stamped in the same UPDATE
+        // so there is never a window where a leased message has no owner
+        let row = sqlx::query(
+            &LeaseSql {
+                set: &format!(
+                    "headers = COALESCE(m.headers, '{{}}'::jsonb) || jsonb_build_object(
+                    '{CLAIMED_BY_HEADER}', $2::text,
+                    '{CLAIMED_AT_HEADER}', clock_timestamp()
+                )"
+                ),
+                ..LeaseSql::new(queue_name)
+            }
+            .sql(),
+        )
+        .bind(vt)
+        .bind(consumer_id)
+        .fetch_optional(executor)
+        .await?;
+        let message = row.as_ref().map(message_from_row).transpose()?;
+        if message.is_some() {
+            ClientCounters::add(&self.counters.read, 1);
+        }
+        Ok(message)
+    }
+
+    /// Read a single message and tag it as claimed by `consumer_id`.
+    ///
+    /// The claim is stored in the message headers under [`CLAIMED_BY_HEADER`] and
+    /// [`CLAIMED_AT_HEADER`], so the current owner of a message can be observed with
+    /// [`claimed_by`](Self::claimed_by) and orphaned claims can be released with
+    /// [`unclaimed_older_than`](Self::unclaimed_older_than).
+    pub async fn read_claim<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        consumer_id: &str,
+        vt: i32,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        self.read_claim_with_cxn(queue_name, consumer_id, vt, &self.connection)
+            .await
+    }
+
+    pub async fn claimed_by_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+        executor: E,
+    ) -> Result<Option<String>, PgmqError> {
+        check_input(queue_name)?;
+        let owner = sqlx::query_scalar::<_, Option<String>>(&format!(
+            "SELECT headers->>'{CLAIMED_BY_HEADER}' FROM pgmq.{QUEUE_PREFIX}_{queue_name} WHERE msg_id = $1::bigint;"
+        ))
+        .bind(msg_id)
+        .fetch_optional(executor)
+        .await?;
+        Ok(owner.flatten())
+    }
+
+    /// The consumer that last claimed a message with [`read_claim`](Self::read_claim), if any.
+    pub async fn claimed_by(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+    ) -> Result<Option<String>, PgmqError> {
+        self.claimed_by_with_cxn(queue_name, msg_id, &self.connection)
+            .await
+    }
+
+    pub async fn unclaimed_older_than_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        older_than: std::time::Duration,
+        executor: E,
+    ) -> Result<Vec<i64>, PgmqError> {
+        check_input(queue_name)?;
+        let reclaimed = sqlx::query_scalar::<_, i64>(&format!(
+            r#"
+            UPDATE pgmq.{QUEUE_PREFIX}_{queue_name}
+            SET
+                vt = clock_timestamp(),
+                headers = headers - '{CLAIMED_BY_HEADER}' - '{CLAIMED_AT_HEADER}'
+            WHERE headers ? '{CLAIMED_BY_HEADER}'
+              AND (headers->>'{CLAIMED_AT_HEADER}')::timestamptz
+                  < clock_timestamp() - make_interval(secs => $1::double precision)
+            RETURNING msg_id
+            "#
+        ))
+        .bind(older_than.as_secs_f64())
+        .fetch_all(executor)
+        .await?;
+        Ok(reclaimed)
+    }
+
+    /// Release claims made more than `older_than` ago, e.g. by a consumer that died.
+    ///
+    /// Reclaimed messages have their claim headers removed and are made visible immediately.
+    /// Returns the ids of the reclaimed messages.
+    pub async fn unclaimed_older_than(
+        &self,
+        queue_name: &str,
+        older_than: std::time::Duration,
+    ) -> Result<Vec<i64>, PgmqError> {
+        self.unclaimed_older_than_with_cxn(queue_name, older_than, &self.connection)
+            .await
+    }
+
+    pub async fn read_lease_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        lease: std::time::Duration,
+        executor: E,
+    ) -> Result<Option<LeasedMessage<T>>, PgmqError> {
+        // round up so the lease is never shorter than requested
+        let secs = lease.as_secs() + u64::from(lease.subsec_nanos() > 0);
+        let vt = i32::try_from(secs).map_err(|_| PgmqError::InvalidDuration {
+            name: "lease",
+            duration: lease,
+            reason: "must be at most i32::MAX seconds",
+        })?;
+        let message = self.read_with_cxn::<_, T>(queue_name, vt, executor).await?;
+        Ok(message.map(|message| LeasedMessage {
+            expires_at: message.vt,
+            message,
+        }))
+    }
+
+    /// Read a single message and lease it for `lease`, rounded up to whole seconds.
+    ///
+    /// The lease is taken in the same statement as the read, and the returned
+    /// [`LeasedMessage::expires_at`] is the exact deadline computed by Postgres. A lease longer
+    /// than `i32::MAX` seconds is rejected with [`PgmqError::InvalidDuration`].
+    pub async fn read_lease<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        lease: std::time::Duration,
+    ) -> Result<Option<LeasedMessage<T>>, PgmqError> {
+        self.read_lease_with_cxn(queue_name, lease, &self.connection)
+            .await
+    }
+
+    pub async fn nack_backoff_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
         &self,
         queue_name: &str,
-        vt: i32,
+        msg: &MessageMeta,
+        base: std::time::Duration,
+        max: std::time::Duration,
+        executor: E,
+    ) -> Result<(), PgmqError> {
+        // base * 2^read_ct, treating overflow as exceeding `max`
+        let delay = u32::try_from(msg.read_ct.max(0))
+            .ok()
+            .and_then(|read_ct| 1u32.checked_shl(read_ct))
+            .and_then(|factor| base.checked_mul(factor))
+            .map_or(max, |delay| delay.min(max));
+        // round up so the backoff is never shorter than computed
+        let secs = delay.as_secs() + u64::from(delay.subsec_nanos() > 0);
+        let vt = i32::try_from(secs).unwrap_or(i32::MAX);
+        self.set_vt_with_cxn::<_, serde_json::Value>(queue_name, msg.msg_id, vt, executor)
+            .await?;
+        Ok(())
+    }
+
+    /// Make a message visible again after a capped exponential backoff.
+    ///
+    /// The new visibility timeout is `min(base * 2^read_ct, max)`, rounded up to whole seconds
+    /// and clamped to `i32::MAX` seconds. A [`Message`] converts into the [`MessageMeta`] taken
+    /// here with `MessageMeta::from(&message)`.
+    pub async fn nack_backoff(
+        &self,
+        queue_name: &str,
+        msg: &MessageMeta,
+        base: std::time::Duration,
+        max: std::time::Duration,
+    ) -> Result<(), PgmqError> {
+        self.nack_backoff_with_cxn(queue_name, msg, base, max, &self.connection)
+            .await
+    }
+
+    pub async fn try_acquire_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        lease: std::time::Duration,
+        executor: E,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        let leased = self
+            .read_lease_with_cxn::<_, T>(queue_name, lease, executor)
+            .await?;
+        Ok(leased.map(|leased| leased.message))
+    }
+
+    /// Try to acquire the sentinel message of a single-message queue, e.g. for leader election.
+    ///
+    /// Returns the message, leased for `lease` (rounded up to whole seconds), if nobody holds
+    /// it, or `None` right away if another holder's lease has not expired. Competing callers
+    /// never block each other. The holder keeps the lease by extending it with `set_vt` before
+    /// `Message::vt`, and releases it early with `set_vt(.., 0)`.
+    pub async fn try_acquire<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        lease: std::time::Duration,
     ) -> Result<Option<Message<T>>, PgmqError> {
-        self.read_with_cxn(queue_name, vt, &self.connection).await
+        self.try_acquire_with_cxn(queue_name, lease, &self.connection)
+            .await
+    }
+
+    /// Read a single message wrapped in a [`MessageGuard`] to [`ack`](MessageGuard::ack) or
+    /// [`nack`](MessageGuard::nack) once it has been processed.
+    pub async fn read_guard<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+    ) -> Result<Option<MessageGuard<T>>, PgmqError> {
+        let message = self.read::<T>(queue_name, vt).await?;
+        Ok(message.map(|message| MessageGuard {
+            queue: self.clone(),
+            queue_name: queue_name.to_owned(),
+            message,
+        }))
+    }
+
+    /// Read a single message and run `handler` on it, extending the message's visibility
+    /// timeout every `heartbeat_interval` until the handler finishes.
+    ///
+    /// The message is read with a visibility timeout of `initial_vt` (rounded up to whole
+    /// seconds), and each heartbeat pushes it back to `initial_vt` from now, so
+    /// `heartbeat_interval` should be well below `initial_vt`. The heartbeat runs as a separate
+    /// future polled alongside the handler, no task is spawned, and the handler never waits on
+    /// it: a slow extension, e.g. while the pool is exhausted, does not delay the handler. A
+    /// failed heartbeat is logged and tried again on the next tick, and the heartbeat is
+    /// cancelled as soon as the handler finishes.
+    ///
+    /// Returns `None` without calling `handler` when no message is visible, and the handler's
+    /// result otherwise. The message is deleted when the handler returns `Ok`. When it returns
+    /// `Err` the message is left alone and becomes visible again once the last extension expires.
+    /// A zero `heartbeat_interval` or an `initial_vt` longer than `i32::MAX` seconds is rejected
+    /// with [`PgmqError::InvalidDuration`].
+    pub async fn process_with_heartbeat<T, R, HE, F, Fut>(
+        &self,
+        queue_name: &str,
+        initial_vt: std::time::Duration,
+        heartbeat_interval: std::time::Duration,
+        handler: F,
+    ) -> Result<Option<Result<R, HE>>, PgmqError>
+    where
+        T: for<'de> Deserialize<'de>,
+        F: FnOnce(Message<T>) -> Fut,
+        Fut: std::future::Future<Output = Result<R, HE>>,
+    {
+        if heartbeat_interval.is_zero() {
+            return Err(PgmqError::InvalidDuration {
+                name: "heartbeat interval",
+                duration: heartbeat_interval,
+                reason: "must be greater than zero",
+            });
+        }
+        // round up so the visibility timeout is never shorter than requested
+        let secs = initial_vt.as_secs() + u64::from(initial_vt.subsec_nanos() > 0);
+        let vt = i32::try_from(secs).map_err(|_| PgmqError::InvalidDuration {
+            name: "initial visibility timeout",
+            duration: initial_vt,
+            reason: "must be at most i32::MAX seconds",
+        })?;
+        let Some(message) = self.read::<T>(queue_name, vt).await? else {
+            return Ok(None);
+        };
+        let msg_id = message.msg_id;
+        // the heartbeat is a future of its own, so a slow `set_vt` never holds up the handler
+        let heartbeat = async {
+            let mut ticks = tokio::time::interval_at(
+                tokio::time::Instant::now() + heartbeat_interval,
+                heartbeat_interval,
+            );
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                if let Err(e) = self
+                    .set_vt::<serde_json::Value>(queue_name, msg_id, vt)
+                    .await
+                {
+                    warn!("failed to extend the visibility timeout of message {msg_id}: {e}");
+                }
+            }
+        };
+        // dropping the heartbeat once the handler finishes cancels it, even mid-`set_vt`
+        let result = tokio::select! {
+            result = handler(message) => result,
+            never = heartbeat => never,
+        };
+        if result.is_ok() {
+            self.delete(queue_name, msg_id).await?;
+        }
+        Ok(Some(result))
     }
 
     pub async fn read_batch_with_poll_with_cxn<
@@ -394,6 +3520,7 @@ impl PGMQueueExt {
         executor: E,
     ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
         check_input(queue_name)?;
+        check_qty(max_batch_size)?;
         let poll_timeout_s = poll_timeout.map_or(DEFAULT_POLL_TIMEOUT_S, |t| t.as_secs() as i32);
         let poll_interval_ms =
             poll_interval.map_or(DEFAULT_POLL_INTERVAL_MS, |i| i.as_millis() as i32);
@@ -416,6 +3543,13 @@ impl PGMQueueExt {
 
         match result {
             Err(sqlx::error::Error::RowNotFound) => Ok(None),
+            Err(sqlx::error::Error::Database(e))
+                if e.code().as_deref() == Some(UNDEFINED_FUNCTION) =>
+            {
+                Err(PgmqError::InstallationError(format!(
+                    "pgmq.read_with_poll is not available, polling requires pgmq >= {MIN_READ_WITH_POLL_VERSION}: {e}"
+                )))
+            }
             Err(e) => Err(e)?,
             Ok(rows) => {
                 // happy path - successfully read messages
@@ -435,77 +3569,662 @@ impl PGMQueueExt {
                         })
                     }
                 }
+                ClientCounters::add(&self.counters.read, messages.len() as u64);
+                Ok(Some(messages))
+            }
+        }
+    }
+
+    /// Read up to `max_batch_size` messages, polling until at least one is visible.
+    ///
+    /// The poll returns as soon as any message can be read, with however many were
+    /// visible at that moment, so a batch may be smaller than `max_batch_size` even
+    /// while more messages are about to arrive. This keeps latency low for
+    /// interactive queues; consumers that favour throughput over latency can
+    /// accumulate partial batches themselves. If nothing becomes visible within
+    /// `poll_timeout` (default 5 seconds), an empty batch is returned.
+    pub async fn read_batch_with_poll<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        max_batch_size: i32,
+        poll_timeout: Option<std::time::Duration>,
+        poll_interval: Option<std::time::Duration>,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
+        let _permit = self.poll_permit().await?;
+        let Some((mut tx, timeout)) = self.begin_poll(poll_timeout).await? else {
+            return self
+                .read_batch_with_poll_with_cxn(
+                    queue_name,
+                    vt,
+                    max_batch_size,
+                    poll_timeout,
+                    poll_interval,
+                    &self.connection,
+                )
+                .await;
+        };
+        let messages = self
+            .read_batch_with_poll_with_cxn(
+                queue_name,
+                vt,
+                max_batch_size,
+                poll_timeout,
+                poll_interval,
+                &mut *tx,
+            )
+            .await
+            .map_err(|e| statement_timed_out(e, timeout))?;
+        tx.commit().await?;
+        Ok(messages)
+    }
+
+    /// Like [`PGMQueueExt::read_batch_with_poll`], returning `Ok(None)` as soon as `cancel`
+    /// completes, e.g. `token.cancelled()` of a tokio-util `CancellationToken`.
+    ///
+    /// Cancelling drops the in-flight poll. The server-side poll may keep running briefly until
+    /// it notices, and if it already took messages they stay invisible until their `vt`
+    /// expires, unless the poll runs in a transaction with a statement timeout (see
+    /// [`PGMQueueExt::with_poll_statement_timeout_margin`]), which is rolled back. Use
+    /// [`PGMQueueExt::consume_batches_until`] to stop a consumer without dropping a poll.
+    pub async fn read_batch_with_poll_until<T, F>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        max_batch_size: i32,
+        poll_timeout: Option<std::time::Duration>,
+        poll_interval: Option<std::time::Duration>,
+        cancel: F,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError>
+    where
+        T: for<'de> Deserialize<'de>,
+        F: std::future::Future<Output = ()>,
+    {
+        tokio::select! {
+            biased;
+            _ = cancel => Ok(None),
+            result = self.read_batch_with_poll(
+                queue_name,
+                vt,
+                max_batch_size,
+                poll_timeout,
+                poll_interval,
+            ) => result,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn read_batch_with_poll_conditional_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        max_batch_size: i32,
+        poll_timeout: Option<std::time::Duration>,
+        poll_interval: Option<std::time::Duration>,
+        conditional: serde_json::Value,
+        executor: E,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
+        check_input(queue_name)?;
+        check_qty(max_batch_size)?;
+        let poll_timeout_s = poll_timeout.map_or(DEFAULT_POLL_TIMEOUT_S, |t| t.as_secs() as i32);
+        let poll_interval_ms =
+            poll_interval.map_or(DEFAULT_POLL_INTERVAL_MS, |i| i.as_millis() as i32);
+        let result = sqlx::query(
+            r#"SELECT msg_id, read_ct, enqueued_at, vt, message from pgmq.read_with_poll(
+                queue_name=>$1::text,
+                vt=>$2::integer,
+                qty=>$3::integer,
+                max_poll_seconds=>$4::integer,
+                poll_interval_ms=>$5::integer,
+                conditional=>$6::jsonb
+            )"#,
+        )
+        .bind(queue_name)
+        .bind(vt)
+        .bind(max_batch_size)
+        .bind(poll_timeout_s)
+        .bind(poll_interval_ms)
+        .bind(conditional)
+        .fetch_all(executor)
+        .await;
+
+        match result {
+            Err(sqlx::error::Error::Database(e))
+                if e.code().as_deref() == Some(UNDEFINED_FUNCTION) =>
+            {
+                Err(PgmqError::InstallationError(format!(
+                    "pgmq.read_with_poll does not accept a conditional filter in the installed pgmq version: {e}"
+                )))
+            }
+            Err(e) => Err(e)?,
+            Ok(rows) => {
+                let messages = rows
+                    .iter()
+                    .map(message_from_row)
+                    .collect::<Result<Vec<Message<T>>, _>>()?;
+                ClientCounters::add(&self.counters.read, messages.len() as u64);
                 Ok(Some(messages))
             }
         }
     }
 
-    pub async fn read_batch_with_poll<T: for<'de> Deserialize<'de>>(
+    /// Same as [`PGMQueueExt::read_batch_with_poll`], but only messages whose body contains
+    /// `conditional` (jsonb `@>`) are read, e.g. `json!({"type": "email"})`.
+    ///
+    /// Messages that don't match are left untouched for other consumers. An empty object
+    /// matches every message.
+    pub async fn read_batch_with_poll_conditional<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        vt: i32,
+        max_batch_size: i32,
+        poll_timeout: Option<std::time::Duration>,
+        poll_interval: Option<std::time::Duration>,
+        conditional: serde_json::Value,
+    ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
+        let _permit = self.poll_permit().await?;
+        let Some((mut tx, timeout)) = self.begin_poll(poll_timeout).await? else {
+            return self
+                .read_batch_with_poll_conditional_with_cxn(
+                    queue_name,
+                    vt,
+                    max_batch_size,
+                    poll_timeout,
+                    poll_interval,
+                    conditional,
+                    &self.connection,
+                )
+                .await;
+        };
+        let messages = self
+            .read_batch_with_poll_conditional_with_cxn(
+                queue_name,
+                vt,
+                max_batch_size,
+                poll_timeout,
+                poll_interval,
+                conditional,
+                &mut *tx,
+            )
+            .await
+            .map_err(|e| statement_timed_out(e, timeout))?;
+        tx.commit().await?;
+        Ok(messages)
+    }
+
+    /// A stream of batches of up to `batch_size` messages, read with
+    /// [`PGMQueueExt::read_batch_with_poll`].
+    ///
+    /// The stream polls until messages arrive and never yields an empty batch, so it only ends
+    /// on error: a retryable error (see [`PgmqError::is_retryable`]) is yielded and polling
+    /// continues, any other error is yielded and ends the stream. Each batch can be
+    /// acknowledged at once with `delete_batch` or `archive_batch`.
+    ///
+    /// Polls that find the queue empty are spaced out with the default [`EmptyQueueBackoff`],
+    /// see [`PGMQueueExt::consume_batches_with_backoff`].
+    pub fn consume_batches<'a, T: for<'de> Deserialize<'de> + 'a>(
+        &'a self,
+        queue_name: &'a str,
+        vt: i32,
+        batch_size: i32,
+    ) -> impl Stream<Item = Result<Vec<Message<T>>, PgmqError>> + 'a {
+        self.consume_batches_with_backoff(queue_name, vt, batch_size, EmptyQueueBackoff::default())
+    }
+
+    /// Like [`PGMQueueExt::consume_batches`], waiting according to `backoff` after each poll
+    /// that finds the queue empty.
+    ///
+    /// An idle consumer otherwise starts a new poll as soon as the previous one times out.
+    /// Pass [`EmptyQueueBackoff::none`] to keep polling without a pause.
+    pub fn consume_batches_with_backoff<'a, T: for<'de> Deserialize<'de> + 'a>(
+        &'a self,
+        queue_name: &'a str,
+        vt: i32,
+        batch_size: i32,
+        backoff: EmptyQueueBackoff,
+    ) -> impl Stream<Item = Result<Vec<Message<T>>, PgmqError>> + 'a {
+        self.consume_batches_until(queue_name, vt, batch_size, backoff, std::future::pending())
+    }
+
+    /// Like [`PGMQueueExt::consume_batches_with_backoff`], ending the stream once `shutdown`
+    /// completes, e.g. `token.cancelled_owned()` of a tokio-util `CancellationToken`.
+    ///
+    /// While the queue is paused with [`PGMQueueExt::pause_queue`] nothing is read and the
+    /// stream checks the flag again after the default poll timeout plus the backoff. The flag
+    /// of a queue that is not paused is only looked up again once every
+    /// [`POLL_TIMEOUT_DEFAULT`], so pausing takes up to that long, plus a poll already in
+    /// progress, to stop the stream.
+    ///
+    /// No new read is started after `shutdown` completes, while a poll that is already in
+    /// progress is allowed to finish and its batch is still yielded before the stream ends.
+    /// Stopping can therefore take up to the poll timeout of
+    /// [`PGMQueueExt::read_batch_with_poll`]. Batches that were yielded but are never deleted
+    /// or archived, because the worker exits before processing them, become visible to other
+    /// consumers again once their `vt` expires.
+    pub fn consume_batches_until<'a, T, F>(
+        &'a self,
+        queue_name: &'a str,
+        vt: i32,
+        batch_size: i32,
+        backoff: EmptyQueueBackoff,
+        shutdown: F,
+    ) -> impl Stream<Item = Result<Vec<Message<T>>, PgmqError>> + 'a
+    where
+        T: for<'de> Deserialize<'de> + 'a,
+        F: std::future::Future<Output = ()> + 'a,
+    {
+        stream::unfold(
+            (Box::pin(shutdown), false, None::<std::time::Instant>),
+            move |(mut shutdown, mut done, mut unpaused_at)| async move {
+                let mut empty_polls = 0u32;
+                loop {
+                    if done || shutdown.as_mut().now_or_never().is_some() {
+                        return None;
+                    }
+                    // a queue seen unpaused is trusted until the flag is due for another look
+                    let due = unpaused_at.is_none_or(|at| at.elapsed() >= PAUSE_CHECK_INTERVAL);
+                    let paused = if due {
+                        self.is_paused(queue_name).await
+                    } else {
+                        Ok(false)
+                    };
+                    let polled = match paused {
+                        Ok(true) => {
+                            unpaused_at = None;
+                            None
+                        }
+                        Ok(false) => {
+                            if due {
+                                unpaused_at = Some(std::time::Instant::now());
+                            }
+                            Some(
+                                self.read_batch_with_poll::<T>(
+                                    queue_name, vt, batch_size, None, None,
+                                )
+                                .await,
+                            )
+                        }
+                        Err(e) => Some(Err(e)),
+                    };
+                    let mut delay = match polled {
+                        Some(Ok(Some(batch))) if !batch.is_empty() => {
+                            return Some((Ok(batch), (shutdown, false, unpaused_at)))
+                        }
+                        Some(Ok(_)) => std::time::Duration::ZERO,
+                        // wait as long as an empty poll would have before checking again
+                        None => POLL_TIMEOUT_DEFAULT,
+                        Some(Err(e)) => {
+                            let retryable = e.is_retryable();
+                            return Some((Err(e), (shutdown, !retryable, unpaused_at)));
+                        }
+                    };
+                    empty_polls = empty_polls.saturating_add(1);
+                    delay += if backoff.jitter {
+                        jittered(backoff.delay(empty_polls))
+                    } else {
+                        backoff.delay(empty_polls)
+                    };
+                    if !delay.is_zero() {
+                        tokio::select! {
+                            _ = tokio::time::sleep(delay) => {}
+                            _ = shutdown.as_mut() => done = true,
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    pub async fn archive_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &(impl AsQueueName + ?Sized),
+        msg_id: i64,
+        executor: E,
+    ) -> Result<bool, PgmqError> {
+        let queue_name = queue_name.as_queue_name()?;
+        let arch =
+            sqlx::query("SELECT * from pgmq.archive(queue_name=>$1::text, msg_id=>$2::bigint)")
+                .bind(queue_name)
+                .bind(msg_id)
+                .fetch_one(executor)
+                .await?;
+        let archived: bool = arch.try_get("archive")?;
+        ClientCounters::add(&self.counters.archived, archived as u64);
+        Ok(archived)
+    }
+    /// Move a message to the archive table.
+    ///
+    /// Returns `false` if the message is not in the queue, which includes a message that was
+    /// already archived or deleted by another consumer. See [`PGMQueueExt::archive_idempotent`].
+    pub async fn archive(
+        &self,
+        queue_name: &(impl AsQueueName + ?Sized),
+        msg_id: i64,
+    ) -> Result<bool, PgmqError> {
+        self.timed(
+            "archive",
+            queue_name,
+            self.archive_with_cxn(queue_name, msg_id, &self.connection),
+        )
+        .await
+    }
+
+    pub async fn archive_idempotent_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+        executor: E,
+    ) -> Result<ArchiveResult, PgmqError> {
+        let archived = self.archive_with_cxn(queue_name, msg_id, executor).await?;
+        Ok(if archived {
+            ArchiveResult::Archived
+        } else {
+            ArchiveResult::NotFound
+        })
+    }
+
+    /// Same as [`PGMQueueExt::archive`], with the outcome spelled out.
+    ///
+    /// A worker that retries an archive after a failure can treat [`ArchiveResult::NotFound`]
+    /// as success: the message is no longer in the queue, whether an earlier attempt archived
+    /// it or another consumer did.
+    pub async fn archive_idempotent(
+        &self,
+        queue_name: &str,
+        msg_id: i64,
+    ) -> Result<ArchiveResult, PgmqError> {
+        self.archive_idempotent_with_cxn(queue_name, msg_id, &self.connection)
+            .await
+    }
+
+    /// Move a slice of messages to the archive table.
+    pub async fn archive_batch_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        msg_ids: &[i64],
+        executor: E,
+    ) -> Result<usize, PgmqError> {
+        check_input(queue_name)?;
+        let qty =
+            sqlx::query("SELECT * from pgmq.archive(queue_name=>$1::text, msg_ids=>$2::bigint[])")
+                .bind(queue_name)
+                .bind(msg_ids)
+                .fetch_all(executor)
+                .await?
+                .len();
+        ClientCounters::add(&self.counters.archived, qty as u64);
+
+        Ok(qty)
+    }
+
+    /// Move a slice of messages to the archive table.
+    pub async fn archive_batch(
+        &self,
+        queue_name: &str,
+        msg_ids: &[i64],
+    ) -> Result<usize, PgmqError> {
+        self.timed(
+            "archive_batch",
+            queue_name,
+            self.archive_batch_with_cxn(queue_name, msg_ids, &self.connection),
+        )
+        .await
+    }
+
+    pub async fn archive_all_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<i64, PgmqError> {
+        check_input(queue_name)?;
+        let archived: i64 = sqlx::query_scalar(&format!(
+            r#"
+            WITH archived AS (
+                DELETE FROM pgmq.{QUEUE_PREFIX}_{queue_name}
+                RETURNING msg_id, vt, read_ct, enqueued_at, last_read_at, message, headers
+            ), inserted AS (
+                INSERT INTO pgmq.{ARCHIVE_PREFIX}_{queue_name} (msg_id, vt, read_ct, enqueued_at, last_read_at, message, headers)
+                SELECT msg_id, vt, read_ct, enqueued_at, last_read_at, message, headers
+                FROM archived
+                RETURNING msg_id
+            )
+            SELECT count(*) FROM inserted
+            "#
+        ))
+        .fetch_one(executor)
+        .await?;
+        ClientCounters::add(&self.counters.archived, archived as u64);
+        Ok(archived)
+    }
+
+    /// Move every message in a queue to its archive table, returning how many were moved.
+    ///
+    /// Messages that are currently invisible are archived too. The move is a single statement,
+    /// so either all messages are archived or none are, and messages sent concurrently are
+    /// either archived or left in the queue, never lost.
+    pub async fn archive_all(&self, queue_name: &str) -> Result<i64, PgmqError> {
+        self.archive_all_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    pub async fn ensure_paused_queues_table_with_cxn<'c, E>(
+        &self,
+        executor: E,
+    ) -> Result<(), PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        let mut tx = executor.begin().await?;
+        // concurrent CREATE TABLE IF NOT EXISTS can still collide in pg_type
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('pgmq.paused_queues'));")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(&format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS pgmq.{PAUSED_QUEUES_TABLE} (
+                queue_name TEXT PRIMARY KEY,
+                paused_at TIMESTAMP WITH TIME ZONE DEFAULT now() NOT NULL
+            );
+            "#
+        ))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Create the shared `pgmq.__paused_queues` table used by [`PGMQueueExt::pause_queue`], if
+    /// it does not exist yet.
+    ///
+    /// Run this once as an install step, e.g. next to [`PGMQueueExt::init`]; `pause_queue`
+    /// does not create the table itself.
+    pub async fn ensure_paused_queues_table(&self) -> Result<(), PgmqError> {
+        self.ensure_paused_queues_table_with_cxn(&self.connection)
+            .await
+    }
+
+    pub async fn pause_queue_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<bool, PgmqError> {
+        check_input(queue_name)?;
+        let paused = sqlx::query(&format!(
+            "INSERT INTO pgmq.{PAUSED_QUEUES_TABLE} (queue_name) VALUES ($1::text) ON CONFLICT DO NOTHING;"
+        ))
+        .bind(queue_name)
+        .execute(executor)
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db) if db.code().as_deref() == Some(UNDEFINED_TABLE) => {
+                PgmqError::InstallationError(format!(
+                    "the paused queues table does not exist, run ensure_paused_queues_table first: {db}"
+                ))
+            }
+            _ => PgmqError::DatabaseError(e),
+        })?
+        .rows_affected();
+        Ok(paused > 0)
+    }
+
+    /// Mark a queue as paused, returning `false` if it already was.
+    ///
+    /// Pausing is advisory: consumers built on [`PGMQueueExt::consume_batches`] stop reading
+    /// the queue until it is resumed, while direct reads and sends are not affected. The flag
+    /// is kept in the shared `pgmq.__paused_queues` table, which must have been created with
+    /// [`PGMQueueExt::ensure_paused_queues_table`], otherwise this returns
+    /// [`PgmqError::InstallationError`].
+    pub async fn pause_queue(&self, queue_name: &str) -> Result<bool, PgmqError> {
+        self.pause_queue_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    pub async fn resume_queue_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<bool, PgmqError> {
+        check_input(queue_name)?;
+        let resumed = sqlx::query(&format!(
+            "DELETE FROM pgmq.{PAUSED_QUEUES_TABLE} WHERE queue_name = $1::text;"
+        ))
+        .bind(queue_name)
+        .execute(executor)
+        .await
+        .map(|done| done.rows_affected())
+        .or_else(no_paused_queues)?;
+        Ok(resumed > 0)
+    }
+
+    /// Resume a queue paused with [`PGMQueueExt::pause_queue`], returning `false` if it was
+    /// not paused.
+    pub async fn resume_queue(&self, queue_name: &str) -> Result<bool, PgmqError> {
+        self.resume_queue_with_cxn(queue_name, &self.connection)
+            .await
+    }
+
+    pub async fn is_paused_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
         &self,
         queue_name: &str,
-        vt: i32,
-        max_batch_size: i32,
-        poll_timeout: Option<std::time::Duration>,
-        poll_interval: Option<std::time::Duration>,
-    ) -> Result<Option<Vec<Message<T>>>, PgmqError> {
-        self.read_batch_with_poll_with_cxn(
-            queue_name,
-            vt,
-            max_batch_size,
-            poll_timeout,
-            poll_interval,
-            &self.connection,
-        )
+        executor: E,
+    ) -> Result<bool, PgmqError> {
+        check_input(queue_name)?;
+        let paused = sqlx::query_scalar(&format!(
+            "SELECT EXISTS(SELECT 1 FROM pgmq.{PAUSED_QUEUES_TABLE} WHERE queue_name = $1::text);"
+        ))
+        .bind(queue_name)
+        .fetch_one(executor)
         .await
+        .or_else(no_paused_queues)?;
+        Ok(paused)
     }
 
-    pub async fn archive_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+    /// Whether a queue is paused, see [`PGMQueueExt::pause_queue`].
+    ///
+    /// Before [`PGMQueueExt::ensure_paused_queues_table`] has run no queue is paused. On a
+    /// caller's transaction, checking before then aborts the transaction like any failed
+    /// statement.
+    pub async fn is_paused(&self, queue_name: &str) -> Result<bool, PgmqError> {
+        self.is_paused_with_cxn(queue_name, &self.connection).await
+    }
+
+    pub async fn ensure_quarantine_table_with_cxn<'c, E>(
         &self,
-        queue_name: &str,
-        msg_id: i64,
         executor: E,
-    ) -> Result<bool, PgmqError> {
-        check_input(queue_name)?;
-        let arch =
-            sqlx::query("SELECT * from pgmq.archive(queue_name=>$1::text, msg_id=>$2::bigint)")
-                .bind(queue_name)
-                .bind(msg_id)
-                .fetch_one(executor)
-                .await?;
-        Ok(arch.try_get("archive")?)
+    ) -> Result<(), PgmqError>
+    where
+        E: sqlx::Acquire<'c, Database = Postgres>,
+    {
+        let mut tx = executor.begin().await?;
+        // concurrent CREATE TABLE IF NOT EXISTS can still collide in pg_type
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('pgmq.quarantine'));")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(&format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS pgmq.{QUARANTINE_TABLE} (
+                id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+                queue_name TEXT NOT NULL,
+                msg_id BIGINT NOT NULL,
+                read_ct INT NOT NULL,
+                enqueued_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                vt TIMESTAMP WITH TIME ZONE NOT NULL,
+                message JSONB,
+                headers JSONB,
+                reason TEXT NOT NULL,
+                quarantined_at TIMESTAMP WITH TIME ZONE DEFAULT now() NOT NULL
+            );
+            "#
+        ))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {QUARANTINE_TABLE}_queue_name_msg_id_idx ON pgmq.{QUARANTINE_TABLE} (queue_name, msg_id);"
+        ))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
     }
-    /// Move a message to the archive table.
-    pub async fn archive(&self, queue_name: &str, msg_id: i64) -> Result<bool, PgmqError> {
-        self.archive_with_cxn(queue_name, msg_id, &self.connection)
+
+    /// Create the shared `pgmq.__quarantine` table used by [`PGMQueueExt::quarantine`], if it
+    /// does not exist yet.
+    ///
+    /// Run this once as an install step, e.g. next to [`PGMQueueExt::init`]; `quarantine` does
+    /// not create the table itself. Rows have a surrogate `id`, so a recreated queue that
+    /// reuses `msg_id`s can quarantine its messages alongside the old ones.
+    pub async fn ensure_quarantine_table(&self) -> Result<(), PgmqError> {
+        self.ensure_quarantine_table_with_cxn(&self.connection)
             .await
     }
 
-    /// Move a slice of messages to the archive table.
-    pub async fn archive_batch_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+    pub async fn quarantine_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
         &self,
         queue_name: &str,
-        msg_ids: &[i64],
+        msg_id: i64,
+        reason: &str,
         executor: E,
-    ) -> Result<usize, PgmqError> {
+    ) -> Result<(), PgmqError> {
         check_input(queue_name)?;
-        let qty =
-            sqlx::query("SELECT * from pgmq.archive(queue_name=>$1::text, msg_ids=>$2::bigint[])")
-                .bind(queue_name)
-                .bind(msg_ids)
-                .fetch_all(executor)
-                .await?
-                .len();
-
-        Ok(qty)
+        sqlx::query(&format!(
+            r#"
+            WITH moved AS (
+                DELETE FROM pgmq.{QUEUE_PREFIX}_{queue_name}
+                WHERE msg_id = $1::bigint
+                RETURNING msg_id, read_ct, enqueued_at, vt, message, headers
+            )
+            INSERT INTO pgmq.{QUARANTINE_TABLE} (queue_name, msg_id, read_ct, enqueued_at, vt, message, headers, reason)
+            SELECT $2::text, msg_id, read_ct, enqueued_at, vt, message, headers, $3::text
+            FROM moved;
+            "#
+        ))
+        .bind(msg_id)
+        .bind(queue_name)
+        .bind(reason)
+        .execute(executor)
+        .await
+        .map_err(|e| quarantine_unavailable(queue_name, e))?;
+        Ok(())
     }
 
-    /// Move a slice of messages to the archive table.
-    pub async fn archive_batch(
+    /// Move a message to the shared `pgmq.__quarantine` table, recording why it was quarantined.
+    ///
+    /// Meant for poison messages, e.g. ones that fail to parse or exceed their retries. The raw
+    /// row is kept along with the queue it came from, so all queues share one place for triage.
+    /// The table name is fixed, see [`QUARANTINE_TABLE`], and the table must have been created
+    /// with [`PGMQueueExt::ensure_quarantine_table`], otherwise this returns
+    /// [`PgmqError::InstallationError`]. Does nothing if the message does not exist.
+    pub async fn quarantine(
         &self,
         queue_name: &str,
-        msg_ids: &[i64],
-    ) -> Result<usize, PgmqError> {
-        self.archive_batch_with_cxn(queue_name, msg_ids, &self.connection)
+        msg_id: i64,
+        reason: &str,
+    ) -> Result<(), PgmqError> {
+        self.quarantine_with_cxn(queue_name, msg_id, reason, &self.connection)
             .await
     }
 
@@ -528,6 +4247,7 @@ impl PGMQueueExt {
                 // happy path - successfully read a message
                 let raw_msg = row.try_get("message")?;
                 let parsed_msg = serde_json::from_value::<T>(raw_msg)?;
+                ClientCounters::add(&self.counters.read, 1);
                 Ok(Some(Message {
                     msg_id: row.try_get("msg_id")?,
                     vt: row.try_get("vt")?,
@@ -547,28 +4267,321 @@ impl PGMQueueExt {
         &self,
         queue_name: &str,
     ) -> Result<Option<Message<T>>, PgmqError> {
-        self.pop_with_cxn(queue_name, &self.connection).await
+        self.timed(
+            "pop",
+            queue_name,
+            self.pop_with_cxn(queue_name, &self.connection),
+        )
+        .await
+    }
+
+    pub async fn pop_archive_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        executor: E,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        let queue_table = format!("pgmq.{QUEUE_PREFIX}_{queue_name}");
+        let archive_table = format!("pgmq.{ARCHIVE_PREFIX}_{queue_name}");
+        // same selection as pgmq.pop, the popped row is moved to the archive in the same statement
+        let row = sqlx::query(&format!(
+            r#"
+            WITH cte AS (
+                SELECT msg_id
+                FROM {queue_table}
+                WHERE vt <= clock_timestamp()
+                ORDER BY msg_id ASC
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            ),
+            popped AS (
+                DELETE FROM {queue_table} m
+                USING cte
+                WHERE m.msg_id = cte.msg_id
+                RETURNING m.msg_id, m.vt, m.read_ct, m.enqueued_at, m.last_read_at, m.message, m.headers
+            ),
+            archived AS (
+                INSERT INTO {archive_table} (msg_id, vt, read_ct, enqueued_at, last_read_at, message, headers)
+                SELECT msg_id, vt, read_ct, enqueued_at, last_read_at, message, headers
+                FROM popped
+            )
+            SELECT msg_id, read_ct, enqueued_at, vt, message FROM popped
+            "#
+        ))
+        .fetch_optional(executor)
+        .await?;
+        let message = row.as_ref().map(message_from_row).transpose()?;
+        if message.is_some() {
+            ClientCounters::add(&self.counters.read, 1);
+            ClientCounters::add(&self.counters.archived, 1);
+        }
+        Ok(message)
+    }
+
+    /// Same as [`PGMQueueExt::pop`], but the message is moved to the archive instead of deleted.
+    ///
+    /// The message is removed from the queue and archived in a single statement, so no other
+    /// consumer can read it in between. Like `pop`, it does not skip messages past their
+    /// [`EXPIRES_AT_HEADER`], only [`read_unexpired`](Self::read_unexpired) does.
+    pub async fn pop_archive<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+    ) -> Result<Option<Message<T>>, PgmqError> {
+        self.pop_archive_with_cxn(queue_name, &self.connection)
+            .await
     }
 
-    pub async fn delete_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+    /// A stream of the messages in a queue's archive with an id greater than `after_msg_id`,
+    /// in id order.
+    ///
+    /// The archive is fetched `batch_size` rows at a time, so memory use stays bounded however
+    /// large the archive is. Each page is a separate query: messages archived while the stream
+    /// is consumed are included if their id is past the current page. The stream ends after the
+    /// last message or the first error.
+    pub fn stream_archive<'a, T: for<'de> Deserialize<'de> + 'a>(
+        &'a self,
+        queue_name: &'a str,
+        after_msg_id: i64,
+        batch_size: i64,
+    ) -> impl Stream<Item = Result<Message<T>, PgmqError>> + 'a {
+        let state = (after_msg_id, std::collections::VecDeque::new(), false);
+        stream::unfold(state, move |(after, mut page, done)| async move {
+            if let Some(message) = page.pop_front() {
+                return Some((Ok(message), (after, page, done)));
+            }
+            if done {
+                return None;
+            }
+            match self.archive_page::<T>(queue_name, after, batch_size).await {
+                Ok(messages) => {
+                    let done = (messages.len() as i64) < batch_size;
+                    let after = messages.last().map_or(after, |m| m.msg_id);
+                    let mut page = std::collections::VecDeque::from(messages);
+                    let message = page.pop_front()?;
+                    Some((Ok(message), (after, page, done)))
+                }
+                Err(e) => Some((Err(e), (after, page, true))),
+            }
+        })
+    }
+
+    /// Write every message of a queue to `writer` as newline-delimited JSON, one
+    /// [`ExportedMessage`] per line, returning the number of lines written.
+    ///
+    /// Messages are written in `msg_id` order, followed by the archived messages when
+    /// `include_archive` is set. Both are read from the same snapshot, so a message archived
+    /// during the export is written exactly once. Rows are streamed, the queue is never held in
+    /// memory as a whole. The output can be restored with [`PGMQueueExt::import_ndjson`].
+    pub async fn export_ndjson<W: AsyncWrite + Unpin>(
+        &self,
+        queue_name: &str,
+        mut writer: W,
+        include_archive: bool,
+    ) -> Result<u64, PgmqError> {
+        check_input(queue_name)?;
+        let mut tables = vec![(format!("pgmq.{QUEUE_PREFIX}_{queue_name}"), false)];
+        if include_archive {
+            tables.push((format!("pgmq.{ARCHIVE_PREFIX}_{queue_name}"), true));
+        }
+        let mut tx = self.connection.begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY;")
+            .execute(&mut *tx)
+            .await?;
+        let mut count = 0;
+        for (table, archived) in tables {
+            let sql = format!(
+                "SELECT msg_id, read_ct, enqueued_at, last_read_at, vt, message, headers, {archived} AS archived FROM {table} ORDER BY msg_id;"
+            );
+            let mut rows = sqlx::query_as::<_, ExportedMessage>(&sql).fetch(&mut *tx);
+            while let Some(message) = rows
+                .try_next()
+                .await
+                .map_err(|e| queue_not_found(queue_name, e))?
+            {
+                let mut line = serde_json::to_vec(&message)?;
+                line.push(b'\n');
+                writer.write_all(&line).await?;
+                count += 1;
+            }
+        }
+        tx.commit().await?;
+        writer.flush().await?;
+        Ok(count)
+    }
+
+    /// Send the messages of newline-delimited JSON written by
+    /// [`PGMQueueExt::export_ndjson`] to a queue, returning the number of messages sent.
+    ///
+    /// Each message is sent with its body and headers, visible immediately. With
+    /// `preserve_ids` it keeps its original `msg_id`, and lines whose id is already in the
+    /// queue are skipped and not counted, so an interrupted import can be re-run; otherwise
+    /// new ids are generated. Archived messages are skipped, they were already processed.
+    /// Blank lines are ignored.
+    ///
+    /// The import runs in a single transaction: the first line that is not a valid
+    /// [`ExportedMessage`] fails it with [`PgmqError::ImportError`] holding the line number,
+    /// and nothing is sent.
+    pub async fn import_ndjson<R: AsyncRead + Unpin>(
+        &self,
+        queue_name: &str,
+        reader: R,
+        preserve_ids: bool,
+    ) -> Result<u64, PgmqError> {
+        check_input(queue_name)?;
+        let mut lines = BufReader::new(reader).lines();
+        let mut tx = self.connection.begin().await?;
+        let mut line_number = 0;
+        let mut count = 0;
+        while let Some(line) = lines.next_line().await? {
+            line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let exported: ExportedMessage =
+                serde_json::from_str(&line).map_err(|e| PgmqError::ImportError {
+                    line: line_number,
+                    reason: e.to_string(),
+                })?;
+            if exported.archived {
+                continue;
+            }
+            let message = exported.message.unwrap_or_default();
+            self.check_message_size(&message)?;
+            let sent = if preserve_ids {
+                insert_with_id(
+                    &mut tx,
+                    queue_name,
+                    exported.msg_id,
+                    &message,
+                    exported.headers.as_ref(),
+                )
+                .await?
+            } else {
+                sqlx::query(
+                    "SELECT pgmq.send(queue_name=>$1::text, msg=>$2::jsonb, headers=>$3::jsonb, delay=>0::integer);",
+                )
+                .bind(queue_name)
+                .bind(&message)
+                .bind(&exported.headers)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| queue_not_found(queue_name, e))?;
+                true
+            };
+            if sent {
+                count += 1;
+            }
+        }
+        tx.commit().await?;
+        ClientCounters::add(&self.counters.sent, count);
+        Ok(count)
+    }
+
+    /// Up to `limit` archived messages with an id greater than `after_msg_id`, in id order.
+    async fn archive_page<T: for<'de> Deserialize<'de>>(
+        &self,
+        queue_name: &str,
+        after_msg_id: i64,
+        limit: i64,
+    ) -> Result<Vec<Message<T>>, PgmqError> {
+        check_input(queue_name)?;
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT msg_id, read_ct, enqueued_at, vt, message
+            FROM pgmq.{ARCHIVE_PREFIX}_{queue_name}
+            WHERE msg_id > $1::bigint
+            ORDER BY msg_id
+            LIMIT $2::bigint
+            "#
+        ))
+        .bind(after_msg_id)
+        .bind(limit)
+        .fetch_all(&self.connection)
+        .await?;
+        rows.iter().map(message_from_row).collect()
+    }
+
+    pub async fn pop_batch_with_cxn<
+        'c,
+        E: sqlx::Executor<'c, Database = Postgres>,
+        T: for<'de> Deserialize<'de>,
+    >(
+        &self,
+        queue_name: &str,
+        qty: i32,
+        executor: E,
+    ) -> Result<PoppedBatch<T>, PgmqError> {
+        check_input(queue_name)?;
+        check_qty(qty)?;
+        let rows = sqlx::query(
+            r#"SELECT msg_id, read_ct, enqueued_at, vt, message from pgmq.pop(queue_name=>$1::text, qty=>$2::integer)"#,
+        )
+        .bind(queue_name)
+        .bind(qty)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| pop_batch_unavailable(queue_name, e))?;
+        let mut messages = rows
+            .iter()
+            .map(message_from_row)
+            .collect::<Result<Vec<Message<T>>, _>>()?;
+        messages.sort_by_key(|m| m.msg_id);
+        ClientCounters::add(&self.counters.read, messages.len() as u64);
+        Ok(PoppedBatch { messages })
+    }
+
+    /// Read up to `qty` messages and delete them at the same time.
+    ///
+    /// The returned batch only contains messages that were actually deleted, see
+    /// [`PoppedBatch::popped_ids`]. Messages are deleted before they are parsed, use
+    /// [`pop_batch_with_cxn`](Self::pop_batch_with_cxn) within a transaction to keep them
+    /// in the queue when parsing fails.
+    ///
+    /// Older pgmq versions, whose `pgmq.pop` takes no `qty`, return
+    /// [`PgmqError::InstallationError`].
+    pub async fn pop_batch<T: for<'de> Deserialize<'de>>(
         &self,
         queue_name: &str,
+        qty: i32,
+    ) -> Result<PoppedBatch<T>, PgmqError> {
+        self.pop_batch_with_cxn(queue_name, qty, &self.connection)
+            .await
+    }
+
+    pub async fn delete_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
+        &self,
+        queue_name: &(impl AsQueueName + ?Sized),
         msg_id: i64,
         executor: E,
     ) -> Result<bool, PgmqError> {
+        let queue_name = queue_name.as_queue_name()?;
         let row =
             sqlx::query("SELECT * from pgmq.delete(queue_name=>$1::text, msg_id=>$2::bigint)")
                 .bind(queue_name)
                 .bind(msg_id)
                 .fetch_one(executor)
                 .await?;
-        Ok(row.try_get("delete")?)
+        let deleted: bool = row.try_get("delete")?;
+        ClientCounters::add(&self.counters.deleted, deleted as u64);
+        Ok(deleted)
     }
 
     // Delete a message by message id.
-    pub async fn delete(&self, queue_name: &str, msg_id: i64) -> Result<bool, PgmqError> {
-        self.delete_with_cxn(queue_name, msg_id, &self.connection)
-            .await
+    pub async fn delete(
+        &self,
+        queue_name: &(impl AsQueueName + ?Sized),
+        msg_id: i64,
+    ) -> Result<bool, PgmqError> {
+        self.timed(
+            "delete",
+            queue_name,
+            self.delete_with_cxn(queue_name, msg_id, &self.connection),
+        )
+        .await
     }
 
     pub async fn delete_batch_with_cxn<'c, E: sqlx::Executor<'c, Database = Postgres>>(
@@ -584,6 +4597,7 @@ impl PGMQueueExt {
                 .fetch_all(executor)
                 .await?
                 .len();
+        ClientCounters::add(&self.counters.deleted, qty as u64);
 
         // FIXME: change function signature to Vec<i64> and return rows
         Ok(qty)
@@ -591,7 +4605,484 @@ impl PGMQueueExt {
 
     // Delete with a slice of message ids
     pub async fn delete_batch(&self, queue_name: &str, msg_id: &[i64]) -> Result<usize, PgmqError> {
-        self.delete_batch_with_cxn(queue_name, msg_id, &self.connection)
+        self.timed(
+            "delete_batch",
+            queue_name,
+            self.delete_batch_with_cxn(queue_name, msg_id, &self.connection),
+        )
+        .await
+    }
+}
+
+/// Pattern of a well-formed [`EXPIRES_AT_HEADER`], an ISO 8601 timestamp like the ones
+/// `send_with_expiry` writes. A value matching it casts to `timestamptz` once its day is checked
+/// against the length of its month.
+const EXPIRES_AT_PATTERN: &str = "^[1-9][0-9]{3}-(0[1-9]|1[0-2])-(0[1-9]|[12][0-9]|3[01])[T ]([01][0-9]|2[0-3]):[0-5][0-9]:[0-5][0-9](\\.[0-9]{1,6})?(Z|[+-](0[0-9]|1[0-5])(:?[0-5][0-9])?)?$";
+
+/// Condition that is true when a message is past its [`EXPIRES_AT_HEADER`].
+///
+/// Messages without the header, or with a malformed one, never expire. The header is only cast
+/// once it is known to be a valid timestamp, so one bad value cannot fail the whole statement.
+fn expired() -> String {
+    let expires_at = format!("headers->>'{EXPIRES_AT_HEADER}'");
+    format!(
+        "CASE \
+         WHEN {expires_at} IS NULL OR {expires_at} !~ '{EXPIRES_AT_PATTERN}' THEN false \
+         WHEN substr({expires_at}, 9, 2)::integer > extract(day from make_date(substr({expires_at}, 1, 4)::integer, substr({expires_at}, 6, 2)::integer, 1) + interval '1 month - 1 day') THEN false \
+         ELSE ({expires_at})::timestamptz <= clock_timestamp() \
+         END"
+    )
+}
+
+/// A read that leases messages in a single statement.
+///
+/// The visible messages of the queue are selected and locked in a CTE, and an `UPDATE` of the
+/// same statement makes them invisible for `$1` seconds. Every read that cannot go through
+/// `pgmq.read` is built from this, so they all pick messages the same way.
+struct LeaseSql<'a> {
+    queue_name: &'a str,
+    /// Extra expressions selected in the CTE, available as `cte.<alias>`.
+    columns: &'a str,
+    order_by: &'a str,
+    limit: &'a str,
+    lock: &'a str,
+    /// Select the oldest message even while it is in flight, and only lease it once visible.
+    head_only: bool,
+    /// Whether the read increments `read_ct` and stamps `last_read_at`.
+    count_read: bool,
+    /// Leave out messages past their [`EXPIRES_AT_HEADER`], the queue table must have a
+    /// `headers` column.
+    unexpired: bool,
+    /// Extra assignments of the `UPDATE`, on the queue row `m`.
+    set: &'a str,
+    returning: &'a str,
+}
+
+impl<'a> LeaseSql<'a> {
+    /// A read of the single oldest visible message, like `pgmq.read` with a `qty` of 1.
+    fn new(queue_name: &'a str) -> Self {
+        LeaseSql {
+            queue_name,
+            columns: "",
+            order_by: "msg_id ASC",
+            limit: "1",
+            lock: "FOR UPDATE SKIP LOCKED",
+            head_only: false,
+            count_read: true,
+            unexpired: false,
+            set: "",
+            returning: "m.msg_id, m.read_ct, m.enqueued_at, m.vt, m.message",
+        }
+    }
+
+    fn sql(&self) -> String {
+        let queue_table = format!("pgmq.{QUEUE_PREFIX}_{}", self.queue_name);
+        let LeaseSql {
+            columns,
+            order_by,
+            limit,
+            lock,
+            returning,
+            ..
+        } = self;
+        let mut conditions = vec![];
+        if self.unexpired {
+            conditions.push(format!("NOT ({})", expired()));
+        }
+        let mut lease_condition = "";
+        if self.head_only {
+            lease_condition = " AND cte.vt <= clock_timestamp()";
+        } else {
+            conditions.push("vt <= clock_timestamp()".to_owned());
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let mut assignments = vec!["vt = clock_timestamp() + make_interval(secs => $1::integer)"];
+        if self.count_read {
+            assignments.push("read_ct = m.read_ct + 1");
+            assignments.push("last_read_at = clock_timestamp()");
+        }
+        if !self.set.is_empty() {
+            assignments.push(self.set);
+        }
+        let assignments = assignments.join(",\n                ");
+        format!(
+            r#"
+            WITH cte AS (
+                SELECT msg_id, vt{columns}
+                FROM {queue_table}
+                {filter}
+                ORDER BY {order_by}
+                LIMIT {limit}
+                {lock}
+            )
+            UPDATE {queue_table} m
+            SET
+                {assignments}
+            FROM cte
+            WHERE m.msg_id = cte.msg_id{lease_condition}
+            RETURNING {returning}
+            "#
+        )
+    }
+}
+
+fn message_from_row<T: for<'de> Deserialize<'de>>(row: &PgRow) -> Result<Message<T>, PgmqError> {
+    let raw_msg = row.try_get("message")?;
+    Ok(Message {
+        msg_id: row.try_get("msg_id")?,
+        vt: row.try_get("vt")?,
+        read_ct: row.try_get("read_ct")?,
+        enqueued_at: row.try_get("enqueued_at")?,
+        message: serde_json::from_value::<T>(raw_msg)?,
+    })
+}
+
+/// Whether the table of paused queues was created yet, it is only created by the first pause.
+/// Treats a missing paused queues table as no queue being paused.
+fn no_paused_queues<T: Default>(err: sqlx::Error) -> Result<T, PgmqError> {
+    match &err {
+        sqlx::Error::Database(e) if e.code().as_deref() == Some(UNDEFINED_TABLE) => {
+            Ok(T::default())
+        }
+        _ => Err(PgmqError::DatabaseError(err)),
+    }
+}
+
+/// The url and pool size for [`PGMQueueExt::from_env`], looking up variables with `var`.
+fn env_config(var: impl Fn(&str) -> Option<String>) -> Result<(String, u32), PgmqError> {
+    let mut problems = vec![];
+    let url = var(DATABASE_URL_ENV).filter(|url| !url.is_empty());
+    if url.is_none() {
+        problems.push(format!("{DATABASE_URL_ENV} is not set"));
+    }
+    let max_connections = match var(MAX_CONNECTIONS_ENV) {
+        None => MAX_CONNECTIONS_DEFAULT,
+        Some(value) => match value.parse::<u32>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                problems.push(format!(
+                    "{MAX_CONNECTIONS_ENV} must be a positive integer, got '{value}'"
+                ));
+                MAX_CONNECTIONS_DEFAULT
+            }
+        },
+    };
+    match url {
+        Some(url) if problems.is_empty() => Ok((url, max_connections)),
+        _ => Err(PgmqError::EnvironmentError { problems }),
+    }
+}
+
+/// Insert a message with an explicit `msg_id` unless one with that id exists, returning
+/// whether it was inserted. The queue's id sequence is advanced past `msg_id`.
+async fn insert_with_id(
+    conn: &mut sqlx::PgConnection,
+    queue_name: &str,
+    msg_id: i64,
+    message: &serde_json::Value,
+    headers: Option<&serde_json::Value>,
+) -> Result<bool, PgmqError> {
+    let inserted = sqlx::query(&format!(
+        r#"
+        INSERT INTO pgmq.{QUEUE_PREFIX}_{queue_name} (msg_id, vt, message, headers)
+        OVERRIDING SYSTEM VALUE
+        VALUES ($1::bigint, clock_timestamp(), $2::jsonb, $3::jsonb)
+        ON CONFLICT (msg_id) DO NOTHING;
+        "#
+    ))
+    .bind(msg_id)
+    .bind(message)
+    .bind(headers)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| queue_not_found(queue_name, e))?
+    .rows_affected()
+        > 0;
+    if inserted {
+        // keep later sends from generating an id that was replayed
+        sqlx::query(
+            r#"
+            SELECT setval(seq, $2::bigint)
+            FROM pg_get_serial_sequence($1::text, 'msg_id') AS seq
+            WHERE $2::bigint > COALESCE(pg_sequence_last_value(seq::regclass), 0);
+            "#,
+        )
+        .bind(format!("pgmq.{QUEUE_PREFIX}_{queue_name}"))
+        .bind(msg_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(inserted)
+}
+
+/// Maps a call to a notify function missing from older pgmq versions to
+/// [`PgmqError::InstallationError`].
+fn notify_insert_unavailable(err: sqlx::Error) -> PgmqError {
+    match &err {
+        sqlx::Error::Database(e) if e.code().as_deref() == Some(UNDEFINED_FUNCTION) => {
+            PgmqError::InstallationError(format!(
+                "notify on insert requires pgmq >= {MIN_NOTIFY_INSERT_VERSION}: {e}"
+            ))
+        }
+        _ => PgmqError::DatabaseError(err),
+    }
+}
+
+/// Error of [`is_known_tag`], recording whether serde rejected the tag as an unknown variant.
+#[derive(Debug)]
+struct TagProbe {
+    unknown_variant: bool,
+}
+
+impl std::fmt::Display for TagProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("tag probe")
+    }
+}
+
+impl std::error::Error for TagProbe {}
+
+impl serde::de::Error for TagProbe {
+    fn custom<M: std::fmt::Display>(_msg: M) -> Self {
+        TagProbe {
+            unknown_variant: false,
+        }
+    }
+
+    fn unknown_variant(_variant: &str, _expected: &'static [&'static str]) -> Self {
+        TagProbe {
+            unknown_variant: true,
+        }
+    }
+}
+
+/// Whether `tag` names a variant of the `#[serde(tag = "type")]` enum `T`.
+///
+/// Deserializes `T` from a map holding nothing but the tag, a known tag only fails later on
+/// the missing fields of its variant.
+fn is_known_tag<T: for<'de> Deserialize<'de>>(tag: &str) -> bool {
+    use serde::de::value::MapDeserializer;
+
+    let probe = MapDeserializer::<_, TagProbe>::new(std::iter::once((MESSAGE_TAG_FIELD, tag)));
+    !matches!(
+        T::deserialize(probe),
+        Err(TagProbe {
+            unknown_variant: true
+        })
+    )
+}
+
+/// Maps a `pgmq.pop` without the `qty` argument to [`PgmqError::InstallationError`], and a
+/// missing queue to [`PgmqError::QueueNotFound`].
+fn pop_batch_unavailable(queue_name: &str, err: sqlx::Error) -> PgmqError {
+    match &err {
+        sqlx::Error::Database(e) if e.code().as_deref() == Some(UNDEFINED_FUNCTION) => {
+            PgmqError::InstallationError(format!(
+                "pop_batch requires a pgmq version whose pgmq.pop accepts qty: {e}"
+            ))
+        }
+        _ => queue_not_found(queue_name, err),
+    }
+}
+
+/// Maps a statement on a queue without the `priority` column to
+/// [`PgmqError::InstallationError`].
+fn priority_unavailable(queue_name: &str, err: sqlx::Error) -> PgmqError {
+    match &err {
+        sqlx::Error::Database(e) if e.code().as_deref() == Some(UNDEFINED_COLUMN) => {
+            PgmqError::InstallationError(format!(
+                "queue {queue_name} has no priority column, run enable_priority first: {e}"
+            ))
+        }
+        _ => queue_not_found(queue_name, err),
+    }
+}
+
+/// Maps a missing quarantine table to [`PgmqError::InstallationError`], and a missing queue to
+/// [`PgmqError::QueueNotFound`].
+fn quarantine_unavailable(queue_name: &str, err: sqlx::Error) -> PgmqError {
+    match &err {
+        sqlx::Error::Database(e)
+            if e.code().as_deref() == Some(UNDEFINED_TABLE)
+                && e.message().contains(QUARANTINE_TABLE) =>
+        {
+            PgmqError::InstallationError(format!(
+                "the quarantine table does not exist, run ensure_quarantine_table first: {e}"
+            ))
+        }
+        _ => queue_not_found(queue_name, err),
+    }
+}
+
+/// Maps a statement cancelled by its `statement_timeout` to [`PgmqError::Timeout`].
+fn statement_timed_out(err: PgmqError, timeout: std::time::Duration) -> PgmqError {
+    match &err {
+        PgmqError::DatabaseError(sqlx::Error::Database(e))
+            if e.code().as_deref() == Some(QUERY_CANCELED) =>
+        {
+            PgmqError::Timeout { timeout }
+        }
+        _ => err,
+    }
+}
+
+/// Renames the queue and archive tables of `from` to those of `to`, along with the indexes
+/// and sequences named after them.
+async fn rename_queue_relations(
+    conn: &mut sqlx::PgConnection,
+    from: &str,
+    to: &str,
+) -> Result<(), PgmqError> {
+    for prefix in [QUEUE_PREFIX, ARCHIVE_PREFIX] {
+        let old_table = format!("{prefix}_{from}").to_lowercase();
+        let new_table = format!("{prefix}_{to}").to_lowercase();
+        let dependents: Vec<(String, bool)> = sqlx::query_as(
+            "SELECT c.relname::text, c.relkind = 'S' FROM pg_depend d JOIN pg_class c ON c.oid = d.objid WHERE d.classid = 'pg_class'::regclass AND d.refobjid = format('pgmq.%I', $1::text)::regclass AND c.relkind IN ('i', 'S');",
+        )
+        .bind(&old_table)
+        .fetch_all(&mut *conn)
+        .await?;
+        for (name, is_sequence) in dependents {
+            let renamed = if let Some(rest) = name.strip_prefix(&format!("{old_table}_")) {
+                format!("{new_table}_{rest}")
+            } else if name == format!("archived_at_idx_{from}") {
+                format!("archived_at_idx_{to}")
+            } else if name == format!("archived_at_idx_{from}").to_lowercase() {
+                // older pgmq versions lowercase the index name like the table names
+                format!("archived_at_idx_{to}").to_lowercase()
+            } else {
+                continue;
+            };
+            let kind = if is_sequence { "SEQUENCE" } else { "INDEX" };
+            sqlx::query(&format!(
+                r#"ALTER {kind} pgmq."{name}" RENAME TO "{renamed}";"#
+            ))
+            .execute(&mut *conn)
+            .await?;
+        }
+        sqlx::query(&format!(
+            r#"ALTER TABLE pgmq."{old_table}" RENAME TO "{new_table}";"#
+        ))
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Maps the error of a statement on a queue that does not exist to [`PgmqError::QueueNotFound`].
+fn queue_not_found(queue_name: &str, err: sqlx::Error) -> PgmqError {
+    match &err {
+        sqlx::Error::Database(e) if e.code().as_deref() == Some(UNDEFINED_TABLE) => {
+            PgmqError::QueueNotFound {
+                name: queue_name.to_owned(),
+            }
+        }
+        _ => PgmqError::DatabaseError(err),
+    }
+}
+
+fn queue_meta_from_row(row: &PgRow) -> Result<PGMQueueMeta, sqlx::Error> {
+    Ok(PGMQueueMeta {
+        queue_name: row.try_get("queue_name")?,
+        created_at: row.try_get("created_at")?,
+        is_unlogged: row.try_get("is_unlogged")?,
+        is_partitioned: row.try_get("is_partitioned")?,
+    })
+}
+
+/// A random duration between half and all of `delay`.
+fn jittered(delay: std::time::Duration) -> std::time::Duration {
+    use std::hash::{BuildHasher, Hasher};
+    // each `RandomState` is seeded differently, which is random enough to spread out polls
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    let fraction = 0.5 + (random % 1000) as f64 / 2000.0;
+    delay.mul_f64(fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FixedClock;
+    use sqlx::postgres::PgPoolOptions;
+
+    #[tokio::test]
+    async fn message_age_uses_clock() {
+        // never connects, the clock is all that is used
+        let pool = PgPoolOptions::new()
+            .connect_lazy("postgres://postgres@localhost/unused")
+            .unwrap();
+        let enqueued_at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let queue = PGMQueueExt::new_with_pool(pool)
             .await
+            .with_clock(FixedClock(enqueued_at + chrono::Duration::seconds(90)));
+        let message = Message {
+            msg_id: 1,
+            vt: enqueued_at,
+            enqueued_at,
+            read_ct: 0,
+            message: serde_json::Value::Null,
+        };
+        assert_eq!(queue.message_age(&message), chrono::Duration::seconds(90));
+        assert_eq!(
+            message.age_at(enqueued_at + chrono::Duration::seconds(5)),
+            chrono::Duration::seconds(5)
+        );
+    }
+
+    #[test]
+    fn known_tags_follow_serde_renames() {
+        #[derive(Deserialize)]
+        #[serde(tag = "type")]
+        #[allow(dead_code)]
+        enum Event {
+            Created {
+                id: u64,
+            },
+            #[serde(rename = "removed")]
+            Deleted,
+        }
+
+        assert!(is_known_tag::<Event>("Created"));
+        assert!(is_known_tag::<Event>("removed"));
+        assert!(!is_known_tag::<Event>("Deleted"));
+        // not a tagged enum, so no tag is ever unknown
+        assert!(is_known_tag::<serde_json::Value>("anything"));
+    }
+
+    #[test]
+    fn env_config_lists_every_problem() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        let (url, max_connections) =
+            env_config(env(&[(DATABASE_URL_ENV, "postgres://localhost/db")])).unwrap();
+        assert_eq!(url, "postgres://localhost/db");
+        assert_eq!(max_connections, MAX_CONNECTIONS_DEFAULT);
+
+        let (_, max_connections) = env_config(env(&[
+            (DATABASE_URL_ENV, "postgres://localhost/db"),
+            (MAX_CONNECTIONS_ENV, "4"),
+        ]))
+        .unwrap();
+        assert_eq!(max_connections, 4);
+
+        let err = env_config(env(&[(MAX_CONNECTIONS_ENV, "many")])).unwrap_err();
+        let PgmqError::EnvironmentError { problems } = err else {
+            panic!("expected an environment error, got {err:?}");
+        };
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains(DATABASE_URL_ENV));
+        assert!(problems[1].contains("'many'"));
     }
 }
@@ -297,6 +297,7 @@ pub fn grant_pgmon_queue(name: CheckedName<'_>) -> Result<String, PgmqError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::util::check_qty;
 
     #[test]
     fn test_grant() {
@@ -374,6 +375,14 @@ $$ LANGUAGE plpgsql;
         assert!(check_input(&"a".repeat(70)).is_err());
     }
 
+    #[test]
+    fn check_qty_rejects_non_positive() {
+        assert!(check_qty(1).is_ok());
+        assert!(check_qty(100).is_ok());
+        assert!(check_qty(0).is_err());
+        assert!(check_qty(-5).is_err());
+    }
+
     #[test]
     fn test_check_input() {
         let invalids = ["bad;queue_name", "bad name", "bad--name"];
@@ -1,3 +1,5 @@
+use crate::errors::PgmqError;
+use crate::util::check_input;
 use serde::Deserialize;
 use sqlx::types::chrono::{DateTime, Utc};
 use std::time::Duration;
@@ -8,6 +10,12 @@ pub const VT_DEFAULT: i32 = 30;
 pub const READ_LIMIT_DEFAULT: i32 = 1;
 pub const POLL_TIMEOUT_DEFAULT: Duration = Duration::from_secs(5);
 pub const POLL_INTERVAL_DEFAULT: Duration = Duration::from_millis(250);
+/// Environment variable holding the connection url, required by `PGMQueueExt::from_env`.
+pub const DATABASE_URL_ENV: &str = "DATABASE_URL";
+/// Environment variable holding the pool size used by `PGMQueueExt::from_env`.
+pub const MAX_CONNECTIONS_ENV: &str = "PGMQ_MAX_CONNECTIONS";
+/// Pool size used by `PGMQueueExt::from_env` when [`MAX_CONNECTIONS_ENV`] is not set.
+pub const MAX_CONNECTIONS_DEFAULT: u32 = 10;
 
 use chrono::serde::ts_seconds::deserialize as from_ts;
 
@@ -15,6 +23,261 @@ pub const QUEUE_PREFIX: &str = r#"q"#;
 pub const ARCHIVE_PREFIX: &str = r#"a"#;
 pub const PGMQ_SCHEMA: &str = "pgmq";
 
+/// Table shared by all queues that `quarantine` moves poison messages to, created by
+/// `PGMQueueExt::ensure_quarantine_table`. The name is fixed.
+pub const QUARANTINE_TABLE: &str = "__quarantine";
+/// Table listing the queues paused with `pause_queue`.
+pub const PAUSED_QUEUES_TABLE: &str = "__paused_queues";
+
+/// Header key carrying the deduplication key of a message sent with `send_upsert`.
+pub const DEDUP_KEY_HEADER: &str = "x-pgmq-dedup-key";
+/// Header key recording which consumer claimed a message with `read_claim`.
+pub const CLAIMED_BY_HEADER: &str = "x-pgmq-claimed-by";
+/// Header key recording when a message was claimed with `read_claim`.
+pub const CLAIMED_AT_HEADER: &str = "x-pgmq-claimed-at";
+/// Header key holding an integer priority, read by `read_by_header_priority`.
+pub const PRIORITY_HEADER: &str = "priority";
+/// Header key holding the timestamp after which a message sent with `send_with_expiry` is
+/// skipped by `read_unexpired` and deleted by `expire_stale`.
+pub const EXPIRES_AT_HEADER: &str = "x-pgmq-expires-at";
+
+/// A queue name that has already been validated.
+///
+/// Construct it once at the boundary and pass `&QueueName` to the hot-path methods of
+/// `PGMQueueExt` (`send`, `read`, `delete`, `archive`) to skip re-validating the name on
+/// every call. Those methods still accept `&str` and validate it each time.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct QueueName(String);
+
+impl QueueName {
+    /// Validate `name`, returning [`PgmqError::InvalidQueueName`] if it is not a valid queue name.
+    pub fn new(name: impl Into<String>) -> Result<Self, PgmqError> {
+        let name = name.into();
+        check_input(&name)?;
+        Ok(Self(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<&str> for QueueName {
+    type Error = PgmqError;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        Self::new(name)
+    }
+}
+
+impl TryFrom<String> for QueueName {
+    type Error = PgmqError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Self::new(name)
+    }
+}
+
+impl AsRef<str> for QueueName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for QueueName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Something that can be used as a queue name, validating it if necessary.
+pub trait AsQueueName {
+    /// The queue name, or [`PgmqError::InvalidQueueName`] if it is not valid.
+    fn as_queue_name(&self) -> Result<&str, PgmqError>;
+}
+
+impl AsQueueName for str {
+    fn as_queue_name(&self) -> Result<&str, PgmqError> {
+        check_input(self)?;
+        Ok(self)
+    }
+}
+
+impl AsQueueName for String {
+    fn as_queue_name(&self) -> Result<&str, PgmqError> {
+        self.as_str().as_queue_name()
+    }
+}
+
+impl AsQueueName for QueueName {
+    fn as_queue_name(&self) -> Result<&str, PgmqError> {
+        Ok(&self.0)
+    }
+}
+
+// so that `&queue_name` works for a `queue_name: &str`
+impl<T: AsQueueName + ?Sized> AsQueueName for &T {
+    fn as_queue_name(&self) -> Result<&str, PgmqError> {
+        (**self).as_queue_name()
+    }
+}
+
+/// Order in which visible messages are delivered by a read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadOrder {
+    /// Deliver messages in `msg_id` order. This is the order used by `pgmq.read`.
+    #[default]
+    MsgId,
+    /// Deliver messages in `enqueued_at` order, breaking ties with `msg_id`.
+    ///
+    /// On partitioned queues `msg_id` order does not always match insertion time across
+    /// partitions, this ordering does. Without an index on `enqueued_at` this requires a
+    /// sort of every visible message on each read.
+    EnqueuedAt,
+}
+
+impl ReadOrder {
+    pub(crate) fn order_by(&self) -> &'static str {
+        match self {
+            ReadOrder::MsgId => "msg_id ASC",
+            ReadOrder::EnqueuedAt => "enqueued_at ASC, msg_id ASC",
+        }
+    }
+}
+
+/// Backoff between polls that find the queue empty, used by
+/// `PGMQueueExt::consume_batches_with_backoff`.
+///
+/// After `n` consecutive empty polls the consumer waits `min(base * 2^(n - 1), max)` before
+/// polling again. The count resets as soon as a poll returns messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmptyQueueBackoff {
+    /// wait after the first empty poll
+    pub base: Duration,
+    /// upper bound for the wait
+    pub max: Duration,
+    /// wait a random duration between half and all of the computed wait, so idle consumers
+    /// started together don't poll in lockstep
+    pub jitter: bool,
+}
+
+impl Default for EmptyQueueBackoff {
+    fn default() -> Self {
+        EmptyQueueBackoff {
+            base: POLL_INTERVAL_DEFAULT,
+            max: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+impl EmptyQueueBackoff {
+    /// No wait between empty polls, the consumer polls again right away.
+    pub fn none() -> Self {
+        EmptyQueueBackoff {
+            base: Duration::ZERO,
+            max: Duration::ZERO,
+            jitter: false,
+        }
+    }
+
+    /// Wait before the next poll after `empty_polls` consecutive empty polls, without jitter.
+    pub fn delay(&self, empty_polls: u32) -> Duration {
+        if empty_polls == 0 {
+            return Duration::ZERO;
+        }
+        1u32.checked_shl(empty_polls - 1)
+            .and_then(|factor| self.base.checked_mul(factor))
+            .map_or(self.max, |delay| delay.min(self.max))
+    }
+}
+
+/// Row locking used by a read to select the messages it takes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadLock {
+    /// Lock the selected rows with `FOR UPDATE SKIP LOCKED`, skipping rows locked by other
+    /// consumers. This is the locking used by `pgmq.read`.
+    #[default]
+    SkipLocked,
+    /// Select rows without locking them.
+    ///
+    /// **Only safe with a single consumer per queue.** With concurrent consumers two reads can
+    /// select the same message and both deliver it. Avoiding the row locks makes reads cheaper
+    /// for a single high-volume consumer.
+    NoLock,
+}
+
+impl ReadLock {
+    pub(crate) fn clause(&self) -> &'static str {
+        match self {
+            ReadLock::SkipLocked => "FOR UPDATE SKIP LOCKED",
+            ReadLock::NoLock => "",
+        }
+    }
+}
+
+/// Condition on the envelope of a message, used by `PGMQueueExt::delete_where`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageFilter {
+    /// Messages read more than this many times, e.g. poison messages that keep failing.
+    ReadCtGreaterThan(i32),
+    /// Messages enqueued before this time.
+    EnqueuedBefore(chrono::DateTime<Utc>),
+    /// Messages last read before this time. Messages that were never read do not match.
+    LastReadBefore(chrono::DateTime<Utc>),
+    /// Messages with a `msg_id` lower than this one.
+    MsgIdBelow(i64),
+}
+
+impl MessageFilter {
+    /// Predicate on the queue table, comparing against the single bind parameter `$1`.
+    pub(crate) fn predicate(&self) -> &'static str {
+        match self {
+            MessageFilter::ReadCtGreaterThan(_) => "read_ct > $1::integer",
+            MessageFilter::EnqueuedBefore(_) => "enqueued_at < $1::timestamptz",
+            MessageFilter::LastReadBefore(_) => "last_read_at < $1::timestamptz",
+            MessageFilter::MsgIdBelow(_) => "msg_id < $1::bigint",
+        }
+    }
+}
+
+/// Counts of operations performed by this process through a client.
+///
+/// These are captured client-side and are independent of the metrics reported by the server.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PgmqClientStats {
+    /// number of messages sent
+    pub sent: u64,
+    /// number of messages read, including popped messages
+    pub read: u64,
+    /// number of messages deleted
+    pub deleted: u64,
+    /// number of messages archived
+    pub archived: u64,
+}
+
+/// Approximate on-disk size of a queue, see `PGMQueueExt::queue_size_bytes`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QueueSize {
+    /// total size of the queue table, including indexes and TOAST
+    pub table_bytes: i64,
+    /// total size of the archive table, including indexes and TOAST
+    pub archive_bytes: i64,
+    /// average stored size of a message body in the queue, `None` if the queue is empty
+    pub avg_message_bytes: Option<f64>,
+}
+
+/// How far consumers are behind on a queue, see `PGMQueueExt::consumer_lag`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConsumerLag {
+    /// number of messages that are visible and waiting to be read
+    pub visible_count: i64,
+    /// time since the oldest visible message was enqueued, `None` if none are visible
+    pub oldest_visible_age: Option<chrono::Duration>,
+    /// number of messages that were read and are not yet visible again, or are delayed
+    pub in_flight_count: i64,
+}
+
 pub struct PGMQueueMeta {
     pub queue_name: String,
     pub is_partitioned: bool,
@@ -39,3 +302,201 @@ pub struct Message<T = serde_json::Value> {
     /// The message body.
     pub message: T,
 }
+
+impl<T> Message<T> {
+    /// How long the message has been in the queue, measured from `enqueued_at` to now.
+    pub fn age(&self) -> chrono::Duration {
+        self.age_at(Utc::now())
+    }
+
+    /// How long the message had been in the queue at `now`.
+    pub fn age_at(&self, now: DateTime<Utc>) -> chrono::Duration {
+        now - self.enqueued_at
+    }
+}
+
+/// Source of the current time for time-dependent client behaviour, see
+/// `PGMQueueExt::message_age`.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// The current UTC time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock, used by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock stopped at a fixed time, for deterministic tests.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Clone, Copy, Debug)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+#[cfg(any(test, feature = "test-util"))]
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// An argument passed to a pgmq function by `PGMQueueExt::call_function`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PgmqArg {
+    /// a `text` argument
+    Text(String),
+    /// an `integer` argument
+    Integer(i32),
+    /// a `bigint` argument
+    BigInt(i64),
+    /// a `bigint[]` argument
+    BigIntArray(Vec<i64>),
+    /// a `boolean` argument
+    Boolean(bool),
+    /// a `jsonb` argument
+    Json(serde_json::Value),
+}
+
+/// Outcome of `PGMQueueExt::send_batch_partial`.
+#[derive(Debug, Default)]
+pub struct BatchSendResult {
+    /// index in the input batch and new message id of every message that was sent
+    pub sent: Vec<(usize, i64)>,
+    /// index in the input batch and error of every message that could not be serialized
+    pub failed: Vec<(usize, serde_json::Error)>,
+}
+
+/// Outcome of `PGMQueueExt::archive_idempotent`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveResult {
+    /// the message was moved to the archive by this call
+    Archived,
+    /// the message is not in the queue, it was already archived or deleted, or never existed
+    NotFound,
+}
+
+/// Kind of queue table, see `QueueSpec`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueueType {
+    /// a regular queue, created with `pgmq.create`
+    #[default]
+    Standard,
+    /// an unlogged queue, created with `pgmq.create_unlogged`
+    Unlogged,
+    /// a queue partitioned with pg_partman, created with `pgmq.create_partitioned`
+    Partitioned,
+}
+
+/// Declarative description of a queue, see `PGMQueueExt::ensure_queues`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueueSpec {
+    /// name of the queue
+    pub name: String,
+    /// kind of queue table to create
+    pub kind: QueueType,
+    /// range covered by each partition of a partitioned queue, either a number of messages
+    /// such as `"10000"` or a time interval such as `"1 day"`. `None` uses the extension default.
+    pub partition_interval: Option<String>,
+    /// how much of a partitioned queue to keep, in the same unit as `partition_interval`.
+    /// `None` uses the extension default.
+    pub retention: Option<String>,
+}
+
+/// Range of message ids currently in a queue, see `PGMQueueExt::msg_id_range`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MsgIdRange {
+    /// smallest `msg_id` in the queue
+    pub min_msg_id: i64,
+    /// largest `msg_id` in the queue
+    pub max_msg_id: i64,
+    /// number of messages in the queue
+    pub count: i64,
+}
+
+impl MsgIdRange {
+    /// Number of ids between `min_msg_id` and `max_msg_id` with no message in the queue,
+    /// i.e. messages that were deleted or archived, or sends that were rolled back.
+    pub fn gaps(&self) -> i64 {
+        self.max_msg_id - self.min_msg_id + 1 - self.count
+    }
+}
+
+/// A partition of a partitioned queue table, see `PGMQueueExt::list_partitions`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartitionInfo {
+    /// name of the partition table
+    pub name: String,
+    /// inclusive lower bound of the partition range, `None` for a default partition
+    pub range_start: Option<String>,
+    /// exclusive upper bound of the partition range, `None` for a default partition
+    pub range_end: Option<String>,
+}
+
+/// Metadata of a message, without its body.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageMeta {
+    /// unique identifier for the message
+    pub msg_id: i64,
+    /// "visibility time". The UTC timestamp at which the message will be available for reading again.
+    pub vt: chrono::DateTime<Utc>,
+    /// UTC timestamp that the message was sent to the queue
+    pub enqueued_at: chrono::DateTime<Utc>,
+    /// The number of times the message has been read. Increments on read.
+    pub read_ct: i32,
+}
+
+impl<T> From<&Message<T>> for MessageMeta {
+    fn from(message: &Message<T>) -> Self {
+        MessageMeta {
+            msg_id: message.msg_id,
+            vt: message.vt,
+            enqueued_at: message.enqueued_at,
+            read_ct: message.read_ct,
+        }
+    }
+}
+
+/// A message read with a lease, see `PGMQueueExt::read_lease`.
+#[derive(Clone, Debug)]
+pub struct LeasedMessage<T = serde_json::Value> {
+    /// The message that was read.
+    pub message: Message<T>,
+    /// UTC timestamp at which the lease expires and the message becomes visible again.
+    pub expires_at: chrono::DateTime<Utc>,
+}
+
+/// One line of the newline-delimited JSON written by `PGMQueueExt::export_ndjson` and read by
+/// `PGMQueueExt::import_ndjson`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, Deserialize, FromRow)]
+pub struct ExportedMessage {
+    pub msg_id: i64,
+    pub read_ct: i32,
+    pub enqueued_at: chrono::DateTime<Utc>,
+    pub last_read_at: Option<chrono::DateTime<Utc>>,
+    pub vt: chrono::DateTime<Utc>,
+    pub message: Option<serde_json::Value>,
+    pub headers: Option<serde_json::Value>,
+    /// whether the message was exported from the archive rather than the queue
+    pub archived: bool,
+}
+
+/// Messages removed from a queue by `PGMQueueExt::pop_batch`.
+#[derive(Clone, Debug)]
+pub struct PoppedBatch<T = serde_json::Value> {
+    /// The popped messages, in `msg_id` order.
+    pub messages: Vec<Message<T>>,
+}
+
+impl<T> PoppedBatch<T> {
+    /// Ids of the messages that were deleted from the queue.
+    ///
+    /// This can be fewer than requested when the queue had fewer visible messages or when
+    /// concurrent consumers took some of them.
+    pub fn popped_ids(&self) -> Vec<i64> {
+        self.messages.iter().map(|m| m.msg_id).collect()
+    }
+}
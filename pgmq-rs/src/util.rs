@@ -15,21 +15,80 @@ use url::{ParseError, Url};
 #[cfg(feature = "cli")]
 use futures_util::stream::StreamExt;
 #[cfg(feature = "cli")]
+use sqlx::Either;
+#[cfg(feature = "cli")]
 use sqlx::Executor;
+#[cfg(feature = "cli")]
+use sqlx::Transaction;
 // Configure connection options
+//
+// A `host` query parameter that is an absolute path connects through the Unix socket in that
+// directory, e.g. `postgres:///dbname?host=/var/run/postgresql&user=postgres`. The user may
+// also be given in the query, and the password is optional for socket connections.
 pub fn conn_options(url: &str) -> Result<PgConnectOptions, ParseError> {
     // Parse url
     let parsed = Url::parse(url)?;
+    let query_param = |name: &str| {
+        parsed
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
     let options = PgConnectOptions::new()
-        .host(parsed.host_str().ok_or(ParseError::EmptyHost)?)
-        .port(parsed.port().ok_or(ParseError::InvalidPort)?)
-        .username(parsed.username())
-        .password(parsed.password().ok_or(ParseError::IdnaError)?)
         .database(parsed.path().trim_start_matches('/'))
         .log_statements(LevelFilter::Debug);
+    let options = match query_param("host").filter(|host| host.starts_with('/')) {
+        Some(socket_dir) => {
+            let port = match query_param("port") {
+                Some(port) => port.parse().map_err(|_| ParseError::InvalidPort)?,
+                None => parsed.port().unwrap_or(5432),
+            };
+            let options = options.socket(socket_dir).port(port);
+            let options = match parsed.password() {
+                Some(password) => options.password(password),
+                None => options,
+            };
+            match query_param("user") {
+                Some(user) => options.username(&user),
+                None if !parsed.username().is_empty() => options.username(parsed.username()),
+                None => options,
+            }
+        }
+        None => options
+            .host(parsed.host_str().ok_or(ParseError::EmptyHost)?)
+            .port(parsed.port().ok_or(ParseError::InvalidPort)?)
+            .username(parsed.username())
+            .password(parsed.password().ok_or(ParseError::IdnaError)?),
+    };
     Ok(options)
 }
 
+/// Build a connection url for the Unix socket in `socket_dir`, see [`conn_options`].
+pub fn socket_url(socket_dir: &std::path::Path, dbname: &str, user: &str) -> String {
+    let mut url = Url::parse("postgres://").expect("static url is valid");
+    url.set_path(&format!("/{dbname}"));
+    url.query_pairs_mut()
+        .append_pair("host", &socket_dir.to_string_lossy())
+        .append_pair("user", user);
+    url.to_string()
+}
+
+/// The connection url with its password replaced by `***`, for use in logs and `Debug` output.
+///
+/// A url that cannot be parsed is redacted entirely, since the password can't be located in it.
+pub fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) => {
+            if parsed.password().is_some() {
+                // only fails for urls that cannot have a password, which this one has
+                let _ = parsed.set_password(Some("***"));
+            }
+            parsed.to_string()
+        }
+        Err(_) => "<redacted>".to_owned(),
+    }
+}
+
 /// Connect to the database
 pub async fn connect(url: &str, max_connections: u32) -> Result<Pool<Postgres>, PgmqError> {
     let options = conn_options(url)?;
@@ -127,6 +186,31 @@ pub fn check_input(input: &str) -> Result<(), PgmqError> {
     }
 }
 
+/// Errors if `key` is not a plain identifier that may be used as a json key.
+pub fn check_json_key(key: &str) -> Result<(), PgmqError> {
+    let valid = !key.is_empty()
+        && key
+            .as_bytes()
+            .iter()
+            .all(|&c| c.is_ascii_alphanumeric() || c == b'_');
+    if valid {
+        Ok(())
+    } else {
+        Err(PgmqError::InvalidJsonKey {
+            key: key.to_owned(),
+        })
+    }
+}
+
+/// Errors if `qty` is not a valid number of messages to read.
+pub fn check_qty(qty: i32) -> Result<(), PgmqError> {
+    if qty >= 1 {
+        Ok(())
+    } else {
+        Err(PgmqError::InvalidQuantity { qty })
+    }
+}
+
 #[cfg(feature = "cli")]
 async fn get_latest_release_tag() -> Result<String, PgmqError> {
     log::info!("Getting latest PGMQ release...");
@@ -148,8 +232,9 @@ async fn get_latest_release_tag() -> Result<String, PgmqError> {
     Ok(release.tag_name)
 }
 
+/// Downloads the install script, returning its name for error messages and its content.
 #[cfg(feature = "cli")]
-async fn get_install_sql(version: Option<&String>) -> Result<String, PgmqError> {
+async fn get_install_sql(version: Option<&String>) -> Result<(String, String), PgmqError> {
     let version_to_use = match version {
         Some(v) => v.clone(),
         None => get_latest_release_tag().await?,
@@ -184,7 +269,7 @@ async fn get_install_sql(version: Option<&String>) -> Result<String, PgmqError>
         return Err(format!("Failed to download SQL file: HTTP {}", response.status()).into());
     }
     let sql_content = response.text().await?;
-    Ok(sql_content)
+    Ok((format!("pgmq.sql@{version_to_use}"), sql_content))
 }
 
 #[cfg(feature = "cli")]
@@ -194,29 +279,71 @@ pub async fn install_pgmq(
 ) -> Result<(), PgmqError> {
     log::info!("Installing PGMQ...");
 
-    let sql_content = get_install_sql(version).await?;
+    let (script, sql_content) = get_install_sql(version).await?;
     // Execute the SQL file
     log::info!("Executing PGMQ installation SQL...");
-    execute_sql_statements(pool, &sql_content).await?;
+    execute_sql_statements(pool.begin().await?, &script, &sql_content).await?;
 
     log::info!("PGMQ installation completed successfully!");
     Ok(())
 }
 
+/// Installs PGMQ into a database that does not have it yet.
+///
+/// Unlike [`install_pgmq`], this errors instead of re-running the install script when a
+/// `pgmq` schema or extension is already present, which is useful for throwaway test databases.
+/// The check and the script run in one transaction.
 #[cfg(feature = "cli")]
-async fn execute_sql_statements(pool: &Pool<Postgres>, multi_query: &str) -> Result<(), Error> {
+pub async fn install_pgmq_fresh(
+    pool: &Pool<Postgres>,
+    version: Option<&String>,
+) -> Result<(), PgmqError> {
     let mut tx = pool.begin().await?;
+    let installed = sqlx::query_scalar::<_, bool>(
+        "SELECT to_regnamespace('pgmq') IS NOT NULL OR EXISTS(SELECT 1 FROM pg_extension WHERE extname = 'pgmq');",
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if installed {
+        return Err(PgmqError::InstallationError(
+            "pgmq is already installed in this database".to_owned(),
+        ));
+    }
+    let (script, sql_content) = get_install_sql(version).await?;
+    execute_sql_statements(tx, &script, &sql_content).await
+}
 
+/// Runs every statement of `script` in `tx`, then commits it.
+///
+/// A failing statement is reported as [`PgmqError::InstallationError`] naming the script and
+/// the 1-based index of the statement.
+#[cfg(feature = "cli")]
+async fn execute_sql_statements(
+    mut tx: Transaction<'_, Postgres>,
+    script: &str,
+    multi_query: &str,
+) -> Result<(), PgmqError> {
     {
         let mut stream = tx.fetch_many(multi_query);
-        // Consume the stream, ignore results, but propagate errors
+        // every statement ends with a query result, rows are only counted towards it
+        let mut completed = 0;
         while let Some(step) = stream.next().await {
-            // Only check for error
-            step?; // If any query fails, this will return the error immediately
+            match step {
+                Ok(Either::Left(_)) => completed += 1,
+                Ok(Either::Right(_)) => {}
+                Err(e) => {
+                    return Err(PgmqError::InstallationError(format!(
+                        "migration '{script}' failed at statement {}: {e}",
+                        completed + 1
+                    )))
+                }
+            }
         }
     }
 
-    tx.commit().await?;
+    tx.commit().await.map_err(|e| {
+        PgmqError::InstallationError(format!("migration '{script}' failed to commit: {e}"))
+    })?;
     Ok(())
 }
 
@@ -1,3 +1,5 @@
+#![allow(deprecated)]
+
 use chrono::{Duration, Utc};
 use pgmq::{
     errors::PgmqError,